@@ -0,0 +1,80 @@
+//! Throughput of `parse_nrbf` over the example dumps and synthetic messages, and of reading a
+//! dump from a file with and without buffering. Run with `cargo bench` from this directory.
+
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::io::BufReader;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nrbf_rs::parse_nrbf;
+use nrbf_rs::value::Value;
+use nrbf_rs::writer::write_nrbf;
+
+const BATIM: &[u8] = include_bytes!("../../examples/batim.dump");
+const DORFROMANTIK: &[u8] = include_bytes!("../../examples/dorfromantik.dump");
+
+/// The number of elements of the synthetic arrays.
+const LENGTH: usize = 1 << 20;
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut stream = Vec::new();
+    write_nrbf(value, &mut stream).unwrap();
+    stream
+}
+
+/// Messages whose root is a large byte array, `Int32` array, `Double` array, string array and
+/// string.
+fn synthetic() -> Vec<(&'static str, Vec<u8>)> {
+    let bytes = Value::Bytes((0..LENGTH).map(|index| index as u8).collect());
+    let ints = Value::array((0..LENGTH).map(|index| Value::I32(index as i32)).collect());
+    let doubles = (0..LENGTH).map(|index| Value::F64(index as f64 / 3.0));
+    let doubles = Value::array(doubles.collect());
+    let strings = (0..LENGTH / 16).map(|index| Value::String(format!("string {index}")));
+    let strings = Value::array(strings.collect());
+    let string = Value::String("x".repeat(LENGTH));
+    vec![
+        ("byte_array", encode(&bytes)),
+        ("int_array", encode(&ints)),
+        ("double_array", encode(&doubles)),
+        ("string_array", encode(&strings)),
+        ("string", encode(&string)),
+    ]
+}
+
+fn parse(c: &mut Criterion) {
+    let mut messages = vec![
+        ("batim", BATIM.to_vec()),
+        ("dorfromantik", DORFROMANTIK.to_vec()),
+    ];
+    messages.extend(synthetic());
+
+    let mut group = c.benchmark_group("parse_nrbf");
+    for (name, message) in &messages {
+        group.throughput(Throughput::Bytes(message.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| parse_nrbf(&mut black_box(message.as_slice())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// The parser issues many small reads, each of which is a system call on a bare `File`.
+fn file(c: &mut Criterion) {
+    let path = std::env::temp_dir().join("nrbf-rs-bench-dorfromantik.dump");
+    fs::write(&path, DORFROMANTIK).unwrap();
+
+    let mut group = c.benchmark_group("parse_nrbf_file");
+    group.throughput(Throughput::Bytes(DORFROMANTIK.len() as u64));
+    group.bench_function("unbuffered", |b| {
+        b.iter(|| parse_nrbf(&mut File::open(&path).unwrap()).unwrap())
+    });
+    group.bench_function("buffered", |b| {
+        b.iter(|| parse_nrbf(&mut BufReader::new(File::open(&path).unwrap())).unwrap())
+    });
+    group.finish();
+
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, parse, file);
+criterion_main!(benches);
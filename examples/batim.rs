@@ -1,9 +1,9 @@
-use std::{io, fs::File};
+use std::{error::Error, fs::File, io::BufReader};
 
 use nrbf_rs::parse_nrbf;
 
-fn main() -> Result<(), io::Error> {
-    let mut stream = File::open("examples/batim.dump")?;
-    println!("{}", parse_nrbf(&mut stream));
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut stream = BufReader::new(File::open("examples/batim.dump")?);
+    println!("{}", parse_nrbf(&mut stream)?);
     Ok(())
 }
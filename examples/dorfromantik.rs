@@ -1,10 +1,10 @@
-use std::{io, fs::File};
+use std::{error::Error, fs::File, io::BufReader};
 
 use nrbf_rs::parse_nrbf;
 
-fn main() -> Result<(), io::Error> {
-    let mut stream = File::open("examples/dorfromantik.dump")?;
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut stream = BufReader::new(File::open("examples/dorfromantik.dump")?);
     // parse_nrbf(&mut stream);
-    println!("{}", parse_nrbf(&mut stream));
+    println!("{}", parse_nrbf(&mut stream)?);
     Ok(())
 }
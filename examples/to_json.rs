@@ -0,0 +1,15 @@
+//! Print an NRBF file as pretty JSON: `cargo run --example to_json --features json -- <path>`.
+
+use std::{env, error::Error, fs::File, io::BufReader};
+
+use nrbf_rs::json::{parse_nrbf_to_json, ReferenceMode};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: to_json <path>")?;
+    let mut stream = BufReader::new(File::open(path)?);
+    // `ById` keeps shared and cyclic objects intact, `Inline` is simpler to read but duplicates
+    // shared objects and fails on cycles.
+    let json = parse_nrbf_to_json(&mut stream, ReferenceMode::ById)?;
+    println!("{}", serde_json::to_string_pretty(&json)?);
+    Ok(())
+}
@@ -0,0 +1,18 @@
+#![no_main]
+
+use std::ptr;
+
+use libfuzzer_sys::fuzz_target;
+use nrbf_rs::ffi::{nrbf_free_string, nrbf_parse};
+use nrbf_rs::json::{parse_nrbf_to_json, ReferenceMode};
+
+// Inlining references must stay within the limits of the decoder, for both the Rust and the C
+// entry points.
+fuzz_target!(|data: &[u8]| {
+    let mut json = ptr::null_mut();
+    unsafe {
+        nrbf_parse(data.as_ptr(), data.len(), &mut json);
+        nrbf_free_string(json);
+    }
+    let _ = parse_nrbf_to_json(&mut &data[..], ReferenceMode::ById);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nrbf_rs::{parse_nrbf_lenient, ParseOptions};
+
+// Recovering from an error must not panic either.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions {
+        max_array_length: Some(1 << 16),
+        ..Default::default()
+    };
+    let _ = parse_nrbf_lenient(&mut &data[..], options);
+});
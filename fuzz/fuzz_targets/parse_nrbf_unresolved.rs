@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nrbf_rs::{parse_nrbf_unresolved, ParseOptions};
+
+// Any input must either parse or fail with a `ParseError`, never panic.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions {
+        max_array_length: Some(1 << 16),
+        ..Default::default()
+    };
+    let _ = parse_nrbf_unresolved(&mut &data[..], options);
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nrbf_rs::{ParseOptions, RecordReader};
+
+// The reader must end after the first error instead of panicking or looping.
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions {
+        max_array_length: Some(1 << 16),
+        ..Default::default()
+    };
+    let mut stream = data;
+    for record in RecordReader::new(&mut stream, options) {
+        if record.is_err() {
+            break;
+        }
+    }
+});
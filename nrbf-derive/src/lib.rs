@@ -0,0 +1,141 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Derives `TryFrom<&Value>` for a struct with named fields. Each field is looked up by name in
+/// the members of a `Value::Object` and converted using its own `TryFrom<&Value>` impl. Use
+/// `#[nrbf(rename = "...")]` for members whose .NET name is not a valid Rust identifier.
+///
+/// For an enum of single-field tuple variants, the variant is chosen by the class name of the
+/// object, which is then converted into the field of the variant. A variant matches the class
+/// named like the variant, or the one given by `#[nrbf(rename = "...")]`, e.g. for namespaced
+/// class names.
+#[proc_macro_derive(FromNrbf, attributes(nrbf))]
+pub fn derive_from_nrbf(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// The name given by `#[nrbf(rename = "...")]` among `attrs`, or `default`.
+fn renamed(attrs: &[syn::Attribute], default: String) -> syn::Result<String> {
+    let mut rename = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("nrbf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("Unknown nrbf attribute"))
+            }
+        })?;
+    }
+    Ok(rename.unwrap_or(default))
+}
+
+fn member_name(field: &syn::Field) -> syn::Result<String> {
+    let ident = field.ident.as_ref().expect("Named fields have identifiers");
+    renamed(&field.attrs, ident.to_string())
+}
+
+/// Convert objects into the variant matching their class name.
+fn expand_enum(name: &syn::Ident, data: &syn::DataEnum) -> syn::Result<proc_macro2::TokenStream> {
+    let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let ident = &variant.ident;
+            if !matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "FromNrbf requires enum variants with a single unnamed field",
+                ));
+            }
+            let class_name = renamed(&variant.attrs, ident.to_string())?;
+            Ok(quote! {
+                #class_name => ::core::convert::TryFrom::try_from(value)
+                    .map(Self::#ident)
+                    .map_err(|error: String| format!("{}: {}", #class_name, error)),
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl<'a> ::core::convert::TryFrom<&'a ::nrbf_rs::value::Value> for #name {
+            type Error = String;
+
+            fn try_from(value: &'a ::nrbf_rs::value::Value) -> Result<Self, String> {
+                let class_name = value
+                    .class_name()
+                    .ok_or_else(|| format!("Expected Object; Got {}", value.type_name()))?;
+                match class_name {
+                    #(#arms)*
+                    other => Err(format!("Unexpected class {}", other)),
+                }
+            }
+        }
+    })
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "FromNrbf cannot be derived for generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Enum(data) => return expand_enum(name, data),
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "FromNrbf requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "FromNrbf can only be derived for structs and enums",
+            ))
+        }
+    };
+
+    let field_inits = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let member = member_name(field)?;
+            Ok(quote! {
+                #ident: {
+                    let member = members
+                        .get(#member)
+                        .ok_or_else(|| format!("Missing member {}", #member))?;
+                    ::core::convert::TryFrom::try_from(member)
+                        .map_err(|error: String| format!("{}: {}", #member, error))?
+                }
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl<'a> ::core::convert::TryFrom<&'a ::nrbf_rs::value::Value> for #name {
+            type Error = String;
+
+            fn try_from(value: &'a ::nrbf_rs::value::Value) -> Result<Self, String> {
+                let members = value
+                    .members()
+                    .ok_or_else(|| format!("Expected Object; Got {}", value.type_name()))?;
+                Ok(Self {
+                    #(#field_inits,)*
+                })
+            }
+        }
+    })
+}
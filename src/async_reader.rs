@@ -0,0 +1,150 @@
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::value::Value;
+use crate::{parse_nrbf_prefix_with_options, OnRecord, ParseError, ParseOptions, RecordTrace};
+
+/// What the decoder thread asks of the task reading the stream.
+enum Request {
+    /// The decoder has used up the previous chunk and needs the next one.
+    Chunk,
+    /// A record has been decoded, for `ParseOptions::on_record`.
+    Record(RecordTrace),
+    /// The decoder has finished, with the root and the length of the message.
+    Done(Result<(Value, u64), ParseError>),
+}
+
+/// Hands the chunks received from the reading task to the decoder, one at a time.
+struct ChunkReader {
+    requests: UnboundedSender<Request>,
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.chunk.len() {
+            let stopped = || io::Error::other("The reading task has stopped");
+            self.requests.send(Request::Chunk).map_err(|_| stopped())?;
+            self.chunk = self.chunks.recv().map_err(|_| stopped())??;
+            self.position = 0;
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Parse an NRBF message from an async reader. The decoder runs on a thread of its own, which is
+/// handed the buffered data of `stream` chunk by chunk, so the message is not buffered as a
+/// whole. A chunk is only consumed from `stream` once the decoder has used it up, so the stream
+/// is left positioned right after the `MessageEnd` record, as with `parse_nrbf`. If parsing
+/// fails, the position of the stream is unspecified.
+///
+/// Wrap readers without a buffer of their own, such as sockets, in a `tokio::io::BufReader`.
+pub async fn parse_nrbf_async<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+) -> Result<Value, ParseError> {
+    parse_nrbf_async_with_options(stream, ParseOptions::default()).await
+}
+
+/// Like `parse_nrbf_async`, with `options`. The memory used for decoding is bounded by their
+/// limits, as with the synchronous parsers. `ParseOptions::on_record` is invoked on the calling
+/// task.
+pub async fn parse_nrbf_async_with_options<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> Result<Value, ParseError> {
+    // The callback need not be `Send`, so it stays here and is fed by the decoder thread, which
+    // is handed the other options.
+    let ParseOptions {
+        mut on_record,
+        on_unknown_record,
+        eager_resolve_known_refs,
+        max_array_length,
+        max_depth,
+        max_values,
+        max_copied_bytes,
+        known_classes,
+        strict,
+        strict_version,
+        preserve_null_runs,
+        keep_reference_ids,
+        root_from_header_id,
+        intern_strings,
+    } = options;
+    let traced = on_record.is_some();
+    let (requests, mut requests_rx) = unbounded_channel();
+    let (chunks, chunks_rx) = mpsc::channel();
+
+    let decoder = thread::spawn(move || {
+        let trace_requests = requests.clone();
+        let options = ParseOptions {
+            on_record: traced.then(|| -> OnRecord {
+                Box::new(move |trace| {
+                    let _ = trace_requests.send(Request::Record(trace.clone()));
+                })
+            }),
+            on_unknown_record,
+            eager_resolve_known_refs,
+            max_array_length,
+            max_depth,
+            max_values,
+            max_copied_bytes,
+            known_classes,
+            strict,
+            strict_version,
+            preserve_null_runs,
+            keep_reference_ids,
+            root_from_header_id,
+            intern_strings,
+        };
+        let mut reader = ChunkReader {
+            requests: requests.clone(),
+            chunks: chunks_rx,
+            chunk: Vec::new(),
+            position: 0,
+        };
+        let result = parse_nrbf_prefix_with_options(&mut reader, options);
+        let _ = requests.send(Request::Done(result));
+    });
+
+    // The number of bytes consumed from `stream`, and of the chunk handed to the decoder last.
+    let mut consumed = 0;
+    let mut chunk_length = 0;
+    loop {
+        let Some(request) = requests_rx.recv().await else {
+            // The decoder thread panicked before sending its result.
+            match decoder.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("The decoder thread finished without a result"),
+            }
+        };
+        match request {
+            Request::Chunk => {
+                // The decoder needs more data, so the whole previous chunk is part of the message.
+                stream.consume(chunk_length);
+                consumed += chunk_length as u64;
+                let chunk = stream.fill_buf().await.map(<[u8]>::to_vec);
+                chunk_length = chunk.as_ref().map_or(0, Vec::len);
+                let _ = chunks.send(chunk);
+            }
+            Request::Record(trace) => {
+                if let Some(on_record) = on_record.as_mut() {
+                    on_record(&trace);
+                }
+            }
+            Request::Done(result) => {
+                let (value, length) = result?;
+                stream.consume((length - consumed) as usize);
+                return Ok(value);
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-pub fn tee<T: std::fmt::Display>(x: T) -> T {
-    // println!("{}", x);
+/// Print the decoded objects and strings to stderr with the `trace` feature, and pass them
+/// through unchanged.
+pub fn tee<T: core::fmt::Display>(x: T) -> T {
+    #[cfg(feature = "trace")]
+    std::eprintln!("{x}");
     x
 }
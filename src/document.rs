@@ -0,0 +1,88 @@
+use crate::prelude::*;
+use crate::value::{Map, Name, Value};
+use crate::{Class, ParseWarning, RecordType};
+
+/// A parsed NRBF message, along with the metadata of its `SerializationHeader`.
+#[derive(Debug, Clone)]
+pub struct ParsedDocument {
+    /// The root of the message, with all references resolved.
+    pub root: Value,
+    pub root_id: i32,
+    /// The type of the record that defined the root, telling e.g. a class instance from an
+    /// array without looking at `root`. `None` if decoding stopped before the root was read.
+    pub root_record_type: Option<RecordType>,
+    pub header_id: i32,
+    pub major_version: i32,
+    pub minor_version: i32,
+    /// The values of all records with an object id, by id. References in these values are kept
+    /// unresolved.
+    pub objects: Map<i32, Value>,
+    /// The class definitions, by the object id of the record defining them. This includes
+    /// `ParseOptions::known_classes`.
+    pub classes: Map<i32, Class>,
+    /// Oddities of the input which did not prevent decoding it, in the order they were found.
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Statistics of the records of a message, see `ParsedDocument::summary`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseSummary {
+    /// The number of records with an object id.
+    pub object_count: usize,
+    /// The number of class instances, by class name.
+    pub class_counts: Map<Name, usize>,
+    pub string_count: usize,
+    pub array_count: usize,
+    /// The total number of elements of all arrays, counting each null of a run.
+    pub array_element_count: usize,
+}
+
+impl ParsedDocument {
+    /// Look up the value of the record with object id `id`.
+    pub fn resolve(&self, id: i32) -> Option<&Value> {
+        self.objects.get(&id)
+    }
+
+    /// Look up the definition of the class of `object` by its class and library name.
+    pub fn class_of(&self, object: &Value) -> Option<&Class> {
+        let (name, library_name) = (object.class_name()?, object.library_name());
+        self.classes
+            .values()
+            .find(|class| class.name() == name && class.library_name() == library_name)
+    }
+
+    /// Collect the strings of all records, ordered by object id. Each record is visited once, so
+    /// a string referenced from several places is collected once, while equal strings of
+    /// different records are collected each.
+    pub fn strings(&self) -> Vec<&str> {
+        let mut ids = self.objects.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.into_iter()
+            .flat_map(|id| self.objects[id].flatten())
+            .filter_map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Count the records of the message by their kind. Each record is counted once, no matter
+    /// how often it is referenced.
+    pub fn summary(&self) -> ParseSummary {
+        let mut summary = ParseSummary {
+            object_count: self.objects.len(),
+            ..Default::default()
+        };
+        for value in self.objects.values() {
+            match value {
+                Value::Object(class_name, _, _) => {
+                    *summary.class_counts.entry(class_name.clone()).or_default() += 1;
+                }
+                Value::String(_) => summary.string_count += 1,
+                Value::Array(..) | Value::Bytes(_) => {
+                    summary.array_count += 1;
+                    summary.array_element_count += value.len().unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+        summary
+    }
+}
@@ -0,0 +1,329 @@
+use alloc::string::FromUtf8Error;
+use core::fmt::Display;
+
+use crate::io;
+use crate::prelude::*;
+
+use crate::{PrimitiveType, RecordType};
+
+/// The number of bytes of an invalid string shown by `ParseErrorKind::InvalidUtf8`.
+const UTF8_PREVIEW_SIZE: usize = 32;
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// Reading from the underlying stream failed, e.g. because it ended prematurely.
+    Io(io::Error),
+    /// A byte did not map to any variant of the enum being read.
+    UnexpectedEnumValue { enum_name: &'static str, byte: u8 },
+    /// A length prefixed string is not valid UTF-8. The error holds the raw bytes of the
+    /// string, which ends at the offset of the `ParseError`.
+    InvalidUtf8(FromUtf8Error),
+    /// An object references a class id which has not been defined by an earlier record.
+    /// `referenced_by_offset` is the offset of the `ClassWithId` record referencing it.
+    ClassNotDefined {
+        class_id: i32,
+        referenced_by_offset: u64,
+    },
+    /// A `ClassWithId` record references an object which is not a class definition, such as an
+    /// instance defined by another `ClassWithId` record, at `referenced_by_offset`.
+    ClassWithIdTargetNotAClass {
+        object_id: i32,
+        referenced_by_offset: u64,
+    },
+    /// With `ParseOptions::strict_version`, the `SerializationHeader` declares a version other
+    /// than 1.0.
+    UnsupportedVersion { major: i32, minor: i32 },
+    /// The stream ended, or the message ended, before a `SerializationHeader` was read.
+    MissingSerializationHeader,
+    /// The stream ended within the `SerializationHeader`. This usually means that the input is
+    /// not an NRBF message at all.
+    TruncatedHeader,
+    /// The record type is known but cannot be decoded. All record types are supported now, so
+    /// this is no longer returned.
+    UnsupportedRecord { record_type: RecordType },
+    /// Values of the primitive type cannot be decoded. All primitive types are supported now, so
+    /// this is no longer returned.
+    UnsupportedPrimitive { primitive_type: PrimitiveType },
+    /// An `ObjectNullMultiple` record covers more nulls than its array or object has elements left.
+    NullRunTooLong { excess: usize },
+    /// A class declares a negative number of members.
+    InvalidMemberCount { member_count: i32 },
+    /// A `BinaryArray` declares fewer than one or more than 32 dimensions.
+    InvalidArrayRank { rank: i32 },
+    /// An array declares a negative length.
+    InvalidArrayLength { length: i32 },
+    /// An `ObjectNullMultiple` record declares a negative number of nulls.
+    InvalidNullCount { count: i32 },
+    /// Two records define an object with the same id.
+    DuplicateObjectId { object_id: i32 },
+    /// The length of a length prefixed string is longer than five bytes or exceeds `i32::MAX`.
+    InvalidStringLength,
+    /// A value has a different primitive type than the record requires.
+    UnexpectedPrimitive {
+        expected: PrimitiveType,
+        found: PrimitiveType,
+    },
+    /// An object contains a reference to itself, directly or through other objects, so its
+    /// references cannot be resolved into a tree.
+    ReferenceCycle { object_id: i32 },
+    /// An array of objects or strings, or a run of nulls, has more elements than
+    /// `ParseOptions::max_array_length` allows.
+    ArrayTooLong { length: usize, limit: usize },
+    /// Records or references are nested deeper than `ParseOptions::max_depth` allows.
+    TooDeep { limit: usize },
+    /// The message decodes to more values than `ParseOptions::max_values` allows.
+    TooManyValues { limit: usize },
+    /// Resolving references copies more bytes than `ParseOptions::max_copied_bytes` allows.
+    TooManyCopiedBytes { limit: usize },
+    /// The product of the lengths of a multidimensional array does not fit into a `usize`.
+    ArraySizeOverflow { lengths: Vec<usize> },
+    /// With `ParseOptions::strict`, a class refers to a library id which no `BinaryLibrary`
+    /// record has declared.
+    UnknownLibrary { library_id: i32 },
+    /// A reference to an object id which no record of the message defines.
+    ObjectNotDefined { object_id: i32 },
+    /// A method message flags values as stored in a call array, but the record following it is
+    /// not an array holding all of them.
+    InvalidCallArray,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseErrorKind::Io(error) => write!(f, "Cannot read from stream: {error}"),
+            ParseErrorKind::UnexpectedEnumValue { enum_name, byte } => {
+                write!(f, "Unexpected {enum_name} value {byte:?}")
+            }
+            ParseErrorKind::InvalidUtf8(error) => {
+                // Show the start of the string, so that e.g. binary data read as a string after
+                // a misread length prefix can be told from a single invalid sequence.
+                let bytes = error.as_bytes();
+                let preview = &bytes[..bytes.len().min(UTF8_PREVIEW_SIZE)];
+                let ellipsis = if bytes.len() > preview.len() { "..." } else { "" };
+                write!(
+                    f,
+                    "Failed to decode UTF8 data: {error}, in the {} byte string b\"{}\"{ellipsis}",
+                    bytes.len(),
+                    preview.escape_ascii()
+                )
+            }
+            ParseErrorKind::ClassNotDefined {
+                class_id,
+                referenced_by_offset,
+            } => write!(
+                f,
+                "Class {class_id} is not yet defined, referenced by the record at {referenced_by_offset}"
+            ),
+            ParseErrorKind::ClassWithIdTargetNotAClass {
+                object_id,
+                referenced_by_offset,
+            } => write!(
+                f,
+                "Object {object_id} is not a class, referenced by the record at {referenced_by_offset}"
+            ),
+            ParseErrorKind::UnsupportedVersion { major, minor } => {
+                write!(f, "Unsupported version {major}.{minor}, expected 1.0")
+            }
+            ParseErrorKind::MissingSerializationHeader => {
+                write!(f, "The message does not contain a SerializationHeader")
+            }
+            ParseErrorKind::TruncatedHeader => write!(
+                f,
+                "The stream ends within the SerializationHeader, it is probably not an NRBF message"
+            ),
+            ParseErrorKind::UnsupportedRecord { record_type } => {
+                write!(f, "Records of type {record_type:?} are not supported")
+            }
+            ParseErrorKind::UnsupportedPrimitive { primitive_type } => {
+                write!(f, "Primitives of type {primitive_type:?} are not supported")
+            }
+            ParseErrorKind::NullRunTooLong { excess } => {
+                write!(
+                    f,
+                    "A run of nulls extends {excess} elements past the end of its container"
+                )
+            }
+            ParseErrorKind::InvalidMemberCount { member_count } => {
+                write!(f, "Invalid member count {member_count}")
+            }
+            ParseErrorKind::InvalidArrayRank { rank } => write!(f, "Invalid array rank {rank}"),
+            ParseErrorKind::InvalidArrayLength { length } => {
+                write!(f, "Invalid array length {length}")
+            }
+            ParseErrorKind::InvalidNullCount { count } => write!(f, "Invalid null count {count}"),
+            ParseErrorKind::DuplicateObjectId { object_id } => {
+                write!(f, "Object {object_id} is defined more than once")
+            }
+            ParseErrorKind::InvalidStringLength => {
+                write!(f, "Invalid length of a length prefixed string")
+            }
+            ParseErrorKind::UnexpectedPrimitive { expected, found } => {
+                write!(f, "Expected a value of type {expected:?}, got {found:?}")
+            }
+            ParseErrorKind::ReferenceCycle { object_id } => {
+                write!(f, "Object {object_id} references itself")
+            }
+            ParseErrorKind::ArrayTooLong { length, limit } => {
+                write!(f, "Array of {length} elements exceeds the limit of {limit}")
+            }
+            ParseErrorKind::TooDeep { limit } => {
+                write!(f, "Records are nested deeper than the limit of {limit}")
+            }
+            ParseErrorKind::TooManyValues { limit } => {
+                write!(f, "The message decodes to more than {limit} values")
+            }
+            ParseErrorKind::TooManyCopiedBytes { limit } => {
+                write!(f, "Copies of shared objects exceed the limit of {limit} bytes")
+            }
+            ParseErrorKind::ArraySizeOverflow { lengths } => {
+                write!(f, "The number of elements of an array of lengths {lengths:?} overflows")
+            }
+            ParseErrorKind::UnknownLibrary { library_id } => {
+                write!(f, "Library {library_id} is not defined")
+            }
+            ParseErrorKind::ObjectNotDefined { object_id } => {
+                write!(f, "Object {object_id} is not defined")
+            }
+            ParseErrorKind::InvalidCallArray => {
+                write!(f, "The call array of a method message is missing or incomplete")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The number of bytes consumed from the stream when the error occurred.
+    pub offset: u64,
+    /// The bytes surrounding `offset`, starting at `context_start`.
+    context: Vec<u8>,
+    context_start: u64,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind) -> Self {
+        ParseError {
+            kind,
+            offset: 0,
+            context: Vec::new(),
+            context_start: 0,
+        }
+    }
+
+    /// Attach the position in the stream and the bytes surrounding it.
+    pub(crate) fn at(self, offset: u64, context_start: u64, context: Vec<u8>) -> Self {
+        ParseError {
+            offset,
+            context,
+            context_start,
+            ..self
+        }
+    }
+
+    /// Move the position `distance` bytes further into the stream, for an error located in a
+    /// message which does not start at the beginning.
+    pub(crate) fn shifted(self, distance: u64) -> Self {
+        ParseError {
+            offset: self.offset + distance,
+            context_start: self.context_start + distance,
+            ..self
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at offset {:#x}", self.kind, self.offset)?;
+        for (row, bytes) in self.context.chunks(16).enumerate() {
+            let row_start = self.context_start + 16 * row as u64;
+            write!(f, "\n  {row_start:08x} ")?;
+            for (index, byte) in bytes.iter().enumerate() {
+                // Mark the first byte that was not consumed.
+                if row_start + index as u64 == self.offset {
+                    write!(f, ">{byte:02x}")?;
+                } else {
+                    write!(f, " {byte:02x}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(error) => Some(error),
+            ParseErrorKind::InvalidUtf8(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> Self {
+        ParseError::new(kind)
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::new(ParseErrorKind::Io(error))
+    }
+}
+
+/// A recoverable oddity of the input, which has been decoded anyway. Collected in
+/// `ParsedDocument::warnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub kind: ParseWarningKind,
+    /// The number of bytes consumed from the stream when the oddity was detected.
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarningKind {
+    /// A class refers to a library id which no `BinaryLibrary` record has declared, so the class
+    /// has no library name. This is an error with `ParseOptions::strict`.
+    UnknownLibrary { library_id: i32 },
+    /// Decoding stopped at a record which cannot be decoded, see `UnknownPolicy::SkipRemaining`.
+    /// `reason` describes the error which would have been returned otherwise.
+    SkippedRemaining { reason: String },
+    /// The call array of a method message holds `count` values which have no field in
+    /// `Value::MethodCall` or `Value::MethodReturn`, such as method signatures, generic
+    /// arguments, exceptions or message properties. They are dropped.
+    IgnoredCallArrayValues { count: usize },
+    /// The `SerializationHeader` declares a version other than 1.0, the message is decoded as
+    /// version 1.0. This is an error with `ParseOptions::strict_version`.
+    UnsupportedVersion { major: i32, minor: i32 },
+}
+
+impl Display for ParseWarningKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseWarningKind::UnknownLibrary { library_id } => {
+                write!(f, "Library {library_id} is not declared")
+            }
+            ParseWarningKind::SkippedRemaining { reason } => {
+                write!(f, "Skipped the rest of the message: {reason}")
+            }
+            ParseWarningKind::IgnoredCallArrayValues { count } => {
+                write!(
+                    f,
+                    "Ignored {count} values of the call array of a method message"
+                )
+            }
+            ParseWarningKind::UnsupportedVersion { major, minor } => {
+                write!(f, "Unsupported version {major}.{minor}, decoded as 1.0")
+            }
+        }
+    }
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at offset {:#x}", self.kind, self.offset)
+    }
+}
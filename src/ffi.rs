@@ -0,0 +1,59 @@
+//! A C interface to the parser, which hands out messages as JSON. To link it into a C or .NET
+//! host, build the crate as a `cdylib` or `staticlib` with the `ffi` feature, e.g. with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+
+use std::ffi::{c_char, CString};
+use std::panic::catch_unwind;
+
+use crate::json::{parse_nrbf_to_json, JsonError, ReferenceMode};
+
+/// The message was parsed, `*out_json` holds its JSON.
+pub const NRBF_OK: i32 = 0;
+/// `ptr` or `out_json` is null, or `len` exceeds `isize::MAX`.
+pub const NRBF_INVALID_ARGUMENT: i32 = 1;
+/// The bytes are not a valid NRBF message, or exceed the limits of `ParseOptions::default()`.
+pub const NRBF_PARSE_ERROR: i32 = 2;
+/// The message cannot be represented as JSON, e.g. because an object references itself.
+pub const NRBF_JSON_ERROR: i32 = 3;
+/// The parser panicked, which is a bug.
+pub const NRBF_PANIC: i32 = 4;
+
+/// Parse the NRBF message in the `len` bytes at `ptr` and convert it to JSON, with references
+/// inlined as by `ReferenceMode::Inline`. Returns one of the `NRBF_` codes. Only on success,
+/// `*out_json` is set to a NUL-terminated string, which must be released with
+/// `nrbf_free_string`.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes, and `out_json` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nrbf_parse(ptr: *const u8, len: usize, out_json: *mut *mut c_char) -> i32 {
+    if ptr.is_null() || out_json.is_null() || len > isize::MAX as usize {
+        return NRBF_INVALID_ARGUMENT;
+    }
+    let bytes = std::slice::from_raw_parts(ptr, len);
+
+    // Unwinding into the host is undefined behavior.
+    let json = match catch_unwind(|| parse_nrbf_to_json(&mut &*bytes, ReferenceMode::Inline)) {
+        Ok(Ok(json)) => json,
+        Ok(Err(JsonError::Parse(_))) => return NRBF_PARSE_ERROR,
+        Ok(Err(_)) => return NRBF_JSON_ERROR,
+        Err(_) => return NRBF_PANIC,
+    };
+    // JSON escapes control characters, so the string contains no NUL.
+    let json = CString::new(json.to_string()).unwrap();
+    *out_json = json.into_raw();
+    NRBF_OK
+}
+
+/// Release a string returned by `nrbf_parse`. Null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null, or a string returned by `nrbf_parse` which has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn nrbf_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
@@ -0,0 +1,82 @@
+//! The parts of `std::io` used for decoding. Without the `std` feature, a minimal replacement is
+//! provided instead, which is implemented for byte slices.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Read, Result};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::{Error, ErrorKind, Read, Result};
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use core::fmt::Display;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// The input ended before all requested bytes were read.
+        UnexpectedEof,
+        /// Any other error of the input.
+        Other,
+    }
+
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+    }
+
+    impl Error {
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error { kind }
+        }
+    }
+
+    impl Display for Error {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self.kind {
+                ErrorKind::UnexpectedEof => write!(f, "unexpected end of input"),
+                ErrorKind::Other => write!(f, "input error"),
+            }
+        }
+    }
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// A source of bytes, mirroring `std::io::Read`.
+    pub trait Read {
+        /// Read up to `buf.len()` bytes, returning how many were read. `Ok(0)` means the input
+        /// has ended.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => return Err(ErrorKind::UnexpectedEof.into()),
+                    n => buf = &mut buf[n..],
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io;
+
+use crate::value::Value;
+use crate::{DecoderState, ParseError, ParseErrorKind, ParseOptions};
+
+/// How references between objects are represented in JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceMode {
+    /// Replace references by the referenced value. Shared values are duplicated, cycles are an
+    /// error. The depth and the size of the copies are limited as by `ParseOptions::default()`,
+    /// exceeding them is a `JsonError::Parse`.
+    Inline,
+    /// Emit `{"root": ..., "objects": {id: ...}}`, where references are `{"$ref": id}` into the
+    /// object table. This represents shared and cyclic values faithfully.
+    ById,
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Parse(ParseError),
+    /// An object (transitively) references itself, which cannot be inlined.
+    Cycle(i32),
+    /// A reference to an object id which is not defined in the message.
+    UndefinedReference(i32),
+    Serialize(serde_json::Error),
+    /// The JSON does not have the shape produced by `to_json`.
+    Deserialize(serde_json::Error),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Parse(error) => write!(f, "{error}"),
+            JsonError::Cycle(id) => write!(f, "Object {id} references itself"),
+            JsonError::UndefinedReference(id) => write!(f, "Object {id} is not defined"),
+            JsonError::Serialize(error) => write!(f, "Cannot serialize to JSON: {error}"),
+            JsonError::Deserialize(error) => write!(f, "Cannot deserialize from JSON: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonError::Parse(error) => Some(error),
+            JsonError::Serialize(error) | JsonError::Deserialize(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for JsonError {
+    fn from(error: ParseError) -> Self {
+        JsonError::Parse(error)
+    }
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError::Serialize(error)
+    }
+}
+
+/// Convert a value to JSON in the representation of its `Serialize` implementation: objects are
+/// maps with their members and a `"$class"` entry, references are `{"$ref": id}`.
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        // Member names are strings and non-finite floats become `null`, so this cannot fail.
+        serde_json::to_value(value).expect("values are representable as JSON")
+    }
+}
+
+/// Resolve all references in `root` to the values in `objects`. This is left to the decoder, so
+/// that the limits of `ParseOptions::default()` apply as when parsing: shared values are copied
+/// into every place referring to them, which deep or widely shared input would otherwise make
+/// exhaust the stack or memory.
+fn inline(root: Value, objects: HashMap<i32, Value>) -> Result<Value, JsonError> {
+    let mut stream: &[u8] = &[];
+    let mut decoder = DecoderState::new(&mut stream, ParseOptions::default());
+    decoder.values = objects;
+    decoder.message_end = true;
+    decoder
+        .resolve_root(root)
+        .map_err(|error| match error.kind {
+            ParseErrorKind::ReferenceCycle { object_id } => JsonError::Cycle(object_id),
+            ParseErrorKind::ObjectNotDefined { object_id } => {
+                JsonError::UndefinedReference(object_id)
+            }
+            _ => JsonError::Parse(error),
+        })
+}
+
+/// `{"$type": type_name, "value": value}`, a value whose variant cannot be told from its JSON.
+fn tagged(type_name: &str, value: impl Into<serde_json::Value>) -> serde_json::Value {
+    serde_json::json!({"$type": type_name, "value": value.into()})
+}
+
+/// A float as a JSON number, or as `"NaN"`, `"Infinity"` or `"-Infinity"`, which JSON numbers
+/// cannot represent.
+fn typed_float(value: f64) -> serde_json::Value {
+    match value {
+        value if value.is_nan() => "NaN".into(),
+        f64::INFINITY => "Infinity".into(),
+        f64::NEG_INFINITY => "-Infinity".into(),
+        value => value.into(),
+    }
+}
+
+/// Convert `value` to JSON like its `Serialize` implementation, tagging the values whose variant
+/// would otherwise be guessed differently when deserializing, see `to_typed_json`.
+fn typed(value: &Value) -> serde_json::Value {
+    let typed_all = |values: &[Value]| values.iter().map(typed).collect::<Vec<_>>();
+    let typed_boxed = |value: &Option<Box<Value>>| value.as_deref().map(typed);
+    match value {
+        Value::U8(v) => tagged("U8", *v),
+        Value::U32(v) => tagged("U32", *v),
+        Value::U64(v) => tagged("U64", *v),
+        Value::I8(v) => tagged("I8", *v),
+        Value::I64(v) => tagged("I64", *v),
+        Value::F32(v) => tagged("F32", typed_float(*v as f64)),
+        Value::F64(v) if !v.is_finite() => tagged("F64", typed_float(*v)),
+        Value::Char(v) => tagged("Char", v.to_string()),
+        Value::TimeSpan(v) => tagged("TimeSpan", *v),
+        Value::Decimal(v) => tagged("Decimal", v.as_str()),
+        Value::Bytes(v) => tagged("Bytes", v.as_slice()),
+        Value::Array(lengths, lower_bounds, values)
+            if lengths.len() != 1 || lower_bounds.iter().any(|bound| *bound != 0) =>
+        {
+            serde_json::json!({
+                "$type": "Array",
+                "lengths": lengths,
+                "lower_bounds": lower_bounds,
+                "values": typed_all(values),
+            })
+        }
+        Value::Array(_, _, values) => typed_all(values).into(),
+        Value::Object(class_name, library_name, members) => {
+            let mut map = serde_json::Map::new();
+            map.insert("$class".to_owned(), (**class_name).into());
+            if let Some(library_name) = library_name {
+                map.insert("$library".to_owned(), (**library_name).into());
+            }
+            for (name, value) in members {
+                map.insert(name.to_string(), typed(value));
+            }
+            map.into()
+        }
+        Value::Referenced(_, value) => typed(value),
+        Value::MethodCall {
+            method_name,
+            type_name,
+            call_context,
+            args,
+        } => serde_json::json!({
+            "$class": "MethodCall",
+            "method_name": method_name,
+            "type_name": type_name,
+            "call_context": typed_boxed(call_context),
+            "args": args.as_deref().map(typed_all),
+        }),
+        Value::MethodReturn {
+            return_value,
+            call_context,
+            args,
+        } => serde_json::json!({
+            "$class": "MethodReturn",
+            "return_value": typed_boxed(return_value),
+            "call_context": typed_boxed(call_context),
+            "args": args.as_deref().map(typed_all),
+        }),
+        other => other.into(),
+    }
+}
+
+/// Convert `root` to JSON, looking up references in `objects`. The JSON is meant to be read:
+/// numbers, strings and sequences do not tell which NRBF type they were, so `from_json` guesses
+/// the types, see `to_typed_json`.
+pub fn to_json(
+    root: &Value,
+    objects: &HashMap<i32, Value>,
+    mode: ReferenceMode,
+) -> Result<serde_json::Value, JsonError> {
+    convert(root, objects, mode, |value| value.into())
+}
+
+/// Like `to_json`, but values whose type `from_json` would guess differently are tagged with it,
+/// as in `{"$type": "U8", "value": 5}`. Arrays which are multidimensional or have lower bounds
+/// are `{"$type": "Array", "lengths": [..], "lower_bounds": [..], "values": [..]}`. Only this form
+/// converts back to the same values, so that a message written from it is accepted by .NET.
+/// `Bool`, `I32`, finite `F64`, `String`, `DateTime` and one-dimensional arrays are untagged, so
+/// that they can be edited as in the untyped form.
+///
+/// The 16-bit integer types are decoded as `I32` and `U32`, and so are written as those.
+pub fn to_typed_json(
+    root: &Value,
+    objects: &HashMap<i32, Value>,
+    mode: ReferenceMode,
+) -> Result<serde_json::Value, JsonError> {
+    convert(root, objects, mode, typed)
+}
+
+fn convert(
+    root: &Value,
+    objects: &HashMap<i32, Value>,
+    mode: ReferenceMode,
+    to_json: impl Fn(&Value) -> serde_json::Value,
+) -> Result<serde_json::Value, JsonError> {
+    match mode {
+        ReferenceMode::Inline => Ok(to_json(&inline(root.clone(), objects.clone())?)),
+        ReferenceMode::ById => {
+            let objects = objects
+                .iter()
+                .map(|(id, value)| (id.to_string(), to_json(value)))
+                .collect::<serde_json::Map<_, _>>();
+            Ok(serde_json::json!({
+                "root": to_json(root),
+                "objects": objects,
+            }))
+        }
+    }
+}
+
+/// Convert JSON produced by `to_json` or `to_typed_json` in the same `mode` back to a value, see
+/// the `Deserialize` implementation of `Value`. In `ById` mode, references are resolved by
+/// inlining the referenced objects, so the result can be written with `write_nrbf`. Only JSON
+/// from `to_typed_json` restores the types of all values.
+pub fn from_json(json: &serde_json::Value, mode: ReferenceMode) -> Result<Value, JsonError> {
+    use serde::de::Error;
+    use serde::Deserialize;
+
+    let value = |json| Value::deserialize(json).map_err(JsonError::Deserialize);
+    match mode {
+        ReferenceMode::Inline => value(json),
+        ReferenceMode::ById => {
+            let invalid = |message| JsonError::Deserialize(serde_json::Error::custom(message));
+            let root = json
+                .get("root")
+                .ok_or_else(|| invalid("Expected a \"root\"".to_owned()))?;
+            let root = value(root)?;
+            let objects = json
+                .get("objects")
+                .and_then(serde_json::Value::as_object)
+                .ok_or_else(|| invalid("Expected an \"objects\" map".to_owned()))?
+                .iter()
+                .map(|(id, object)| {
+                    let id = id
+                        .parse()
+                        .map_err(|_| invalid(format!("Unexpected object id {id:?}")))?;
+                    Ok((id, value(object)?))
+                })
+                .collect::<Result<HashMap<i32, Value>, JsonError>>()?;
+            inline(root, objects)
+        }
+    }
+}
+
+/// Parse an NRBF message and convert it to JSON with `to_json`.
+pub fn parse_nrbf_to_json<R: io::Read>(
+    stream: &mut R,
+    mode: ReferenceMode,
+) -> Result<serde_json::Value, JsonError> {
+    parse_and_convert(stream, mode, |value| value.into())
+}
+
+/// Parse an NRBF message and convert it to JSON with `to_typed_json`, to be edited and written
+/// back with `from_json` and `write_nrbf`.
+pub fn parse_nrbf_to_typed_json<R: io::Read>(
+    stream: &mut R,
+    mode: ReferenceMode,
+) -> Result<serde_json::Value, JsonError> {
+    parse_and_convert(stream, mode, typed)
+}
+
+fn parse_and_convert<R: io::Read>(
+    stream: &mut R,
+    mode: ReferenceMode,
+    to_json: impl Fn(&Value) -> serde_json::Value,
+) -> Result<serde_json::Value, JsonError> {
+    let (root, objects) = parse_unresolved(stream)?;
+    match mode {
+        // The values are not needed afterwards, so they are resolved without copying them first.
+        ReferenceMode::Inline => Ok(to_json(&inline(root, objects)?)),
+        ReferenceMode::ById => convert(&root, &objects, mode, to_json),
+    }
+}
+
+fn parse_unresolved<R: io::Read>(
+    stream: &mut R,
+) -> Result<(Value, HashMap<i32, Value>), JsonError> {
+    let mut decoder = DecoderState::new(stream, ParseOptions::default());
+    let result = decoder.parse_unresolved();
+    Ok(result.map_err(|error| decoder.stream.locate(error))?)
+}
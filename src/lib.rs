@@ -1,35 +1,84 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
 use debug::tee;
+pub use document::{ParseSummary, ParsedDocument};
+pub use error::{ParseError, ParseErrorKind, ParseWarning, ParseWarningKind};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+pub use options::{OnRecord, ParseOptions, RecordTrace, UnknownPolicy};
+use prelude::*;
 use primitives::{
-    read_f32, read_f64, read_i16, read_i32, read_i64, read_i8, read_lps, read_u16, read_u32,
-    read_u64, read_u8,
+    peek_u8, read_byte_array, read_char, read_f32, read_f64, read_i16, read_i32, read_i64, read_i8,
+    read_u16, read_u32, read_u64, read_u8,
 };
-use std::collections::HashMap;
-use std::io;
-use value::Value;
+pub use primitives::{read_lps, read_variable_length};
+use reader::CountingReader;
+pub use record_reader::RecordReader;
+use value::{DateTimeKind, Map, Name, Value};
+pub use visit::{parse_nrbf_visit, Visitor};
+
+#[cfg(feature = "tokio")]
+pub use async_reader::{parse_nrbf_async, parse_nrbf_async_with_options};
+#[cfg(feature = "derive")]
+pub use nrbf_derive::FromNrbf;
 
+#[cfg(feature = "tokio")]
+mod async_reader;
 mod debug;
+mod document;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod io;
+#[cfg(feature = "json")]
+pub mod json;
+mod options;
 mod primitives;
+mod reader;
+mod record_reader;
 pub mod value;
+mod visit;
+#[cfg(feature = "std")]
+pub mod writer;
+
+/// The `alloc` items which `std` provides through its prelude.
+mod prelude {
+    pub use alloc::borrow::ToOwned;
+    pub use alloc::boxed::Box;
+    pub use alloc::format;
+    pub use alloc::string::String;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
 
-trait FromStream {
-    fn from_stream<R: io::Read>(stream: &mut R) -> Self;
+trait FromStream: Sized {
+    fn from_stream<R: io::Read>(stream: &mut R) -> Result<Self, ParseError>;
 }
 
 // The following makes all `FromPrimitive` enums readable directly from stream.
 impl<T: FromPrimitive> FromStream for T {
-    fn from_stream<R: io::Read>(stream: &mut R) -> Self {
-        let byte = read_u8(stream);
-        match FromPrimitive::from_u8(byte) {
-            Some(enum_val) => enum_val,
-            None => panic!("Unexpected enum value {byte:?}"),
-        }
+    fn from_stream<R: io::Read>(stream: &mut R) -> Result<Self, ParseError> {
+        enum_from_u8(read_u8(stream)?)
     }
 }
 
-#[derive(Debug, FromPrimitive)]
-enum RecordType {
+fn enum_from_u8<T: FromPrimitive>(byte: u8) -> Result<T, ParseError> {
+    FromPrimitive::from_u8(byte).ok_or_else(|| {
+        let type_name = core::any::type_name::<T>();
+        ParseErrorKind::UnexpectedEnumValue {
+            enum_name: type_name.rsplit("::").next().unwrap_or(type_name),
+            byte,
+        }
+        .into()
+    })
+}
+
+#[derive(Debug, FromPrimitive, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
     SerializationHeader = 0,
     ClassWithId = 1,
     SystemClassWithMembers = 2,
@@ -52,8 +101,9 @@ enum RecordType {
     MethodReturn = 22,
 }
 
-#[derive(Debug, FromPrimitive, Clone)]
-enum BinaryType {
+/// The type of a class member or array element, which determines how its value is encoded.
+#[derive(Debug, FromPrimitive, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryType {
     Primitive = 0,
     String = 1,
     Object = 2,
@@ -62,11 +112,33 @@ enum BinaryType {
     ObjectArray = 5,
     StringArray = 6,
     PrimitiveArray = 7,
-    Record, // Additional field, not in spec.
 }
 
-#[derive(Debug, FromPrimitive, Clone)]
-enum PrimitiveType {
+/// Converts the enums of the format from and to the byte they are encoded as.
+macro_rules! impl_byte_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<u8> for $t {
+                type Error = ParseError;
+
+                fn try_from(byte: u8) -> Result<Self, ParseError> {
+                    enum_from_u8(byte)
+                }
+            }
+
+            impl From<$t> for u8 {
+                fn from(value: $t) -> u8 {
+                    value as u8
+                }
+            }
+        )*
+    };
+}
+
+impl_byte_conversions!(RecordType, BinaryType, PrimitiveType);
+
+#[derive(Debug, FromPrimitive, Clone, Copy, PartialEq, Eq)]
+pub enum PrimitiveType {
     Boolean = 1,
     Byte = 2,
     Char = 3,
@@ -87,30 +159,83 @@ enum PrimitiveType {
 }
 
 impl PrimitiveType {
-    fn read<R: io::Read>(&self, stream: &mut R) -> Value {
+    /// The encoded size of fixed-size primitives which can be decoded directly from a buffer.
+    fn size(&self) -> Option<usize> {
         match self {
-            PrimitiveType::Boolean => Value::Bool(read_u8(stream) != 0),
-            // case PrimitiveType.Char:
-            // case PrimitiveType.Decimal:
-            // case PrimitiveType.TimeSpan :
-            // case PrimitiveType.DateTime:
-            PrimitiveType::SByte => Value::I8(read_i8(stream)),
-            PrimitiveType::Int16 => Value::I32(read_i16(stream) as i32),
-            PrimitiveType::Int32 => Value::I32(read_i32(stream)),
-            PrimitiveType::Int64 => Value::I64(read_i64(stream)),
-            PrimitiveType::Byte => Value::U8(read_u8(stream)),
-            PrimitiveType::UInt16 => Value::U32(read_u16(stream) as u32),
-            PrimitiveType::UInt32 => Value::U32(read_u32(stream)),
-            PrimitiveType::UInt64 => Value::U64(read_u64(stream)),
-            PrimitiveType::Single => Value::F32(read_f32(stream)),
-            PrimitiveType::Double => Value::F64(read_f64(stream)),
-            PrimitiveType::Null => Value::Null,
-            PrimitiveType::String => Value::String(read_lps(stream)),
-            _ => panic!("Cannot deserialize {self:?} yet"),
+            PrimitiveType::Boolean | PrimitiveType::Byte | PrimitiveType::SByte => Some(1),
+            PrimitiveType::Int16 | PrimitiveType::UInt16 => Some(2),
+            PrimitiveType::Int32 | PrimitiveType::UInt32 | PrimitiveType::Single => Some(4),
+            PrimitiveType::Int64
+            | PrimitiveType::UInt64
+            | PrimitiveType::Double
+            | PrimitiveType::DateTime
+            | PrimitiveType::TimeSpan => Some(8),
+            _ => None,
         }
     }
+
+    /// Decode a single value of a fixed-size primitive, `bytes` must be exactly `self.size()` long.
+    fn decode(&self, bytes: &[u8]) -> Value {
+        fn le<const N: usize>(bytes: &[u8]) -> [u8; N] {
+            bytes.try_into().unwrap()
+        }
+
+        match self {
+            PrimitiveType::Boolean => Value::Bool(bytes[0] != 0),
+            PrimitiveType::Byte => Value::U8(bytes[0]),
+            PrimitiveType::SByte => Value::I8(bytes[0] as i8),
+            PrimitiveType::Int16 => Value::I32(i16::from_le_bytes(le(bytes)) as i32),
+            PrimitiveType::UInt16 => Value::U32(u16::from_le_bytes(le(bytes)) as u32),
+            PrimitiveType::Int32 => Value::I32(i32::from_le_bytes(le(bytes))),
+            PrimitiveType::UInt32 => Value::U32(u32::from_le_bytes(le(bytes))),
+            PrimitiveType::Single => Value::F32(f32::from_le_bytes(le(bytes))),
+            PrimitiveType::Int64 => Value::I64(i64::from_le_bytes(le(bytes))),
+            PrimitiveType::UInt64 => Value::U64(u64::from_le_bytes(le(bytes))),
+            PrimitiveType::Double => Value::F64(f64::from_le_bytes(le(bytes))),
+            PrimitiveType::DateTime => date_time(u64::from_le_bytes(le(bytes))),
+            PrimitiveType::TimeSpan => Value::TimeSpan(i64::from_le_bytes(le(bytes))),
+            _ => unreachable!("{self:?} has no fixed size"),
+        }
+    }
+
+    fn read<R: io::Read>(&self, stream: &mut R) -> Result<Value, ParseError> {
+        Ok(match self {
+            PrimitiveType::Boolean => Value::Bool(read_u8(stream)? != 0),
+            PrimitiveType::Char => Value::Char(read_char(stream)?),
+            PrimitiveType::TimeSpan => Value::TimeSpan(read_i64(stream)?),
+            PrimitiveType::DateTime => date_time(read_u64(stream)?),
+            PrimitiveType::SByte => Value::I8(read_i8(stream)?),
+            PrimitiveType::Int16 => Value::I32(read_i16(stream)? as i32),
+            PrimitiveType::Int32 => Value::I32(read_i32(stream)?),
+            PrimitiveType::Int64 => Value::I64(read_i64(stream)?),
+            PrimitiveType::Byte => Value::U8(read_u8(stream)?),
+            PrimitiveType::UInt16 => Value::U32(read_u16(stream)? as u32),
+            PrimitiveType::UInt32 => Value::U32(read_u32(stream)?),
+            PrimitiveType::UInt64 => Value::U64(read_u64(stream)?),
+            PrimitiveType::Single => Value::F32(read_f32(stream)?),
+            PrimitiveType::Double => Value::F64(read_f64(stream)?),
+            PrimitiveType::Null => Value::Null,
+            PrimitiveType::String => Value::String(read_lps(stream)?),
+            // Decimals are stored as strings, e.g. "-12.50".
+            PrimitiveType::Decimal => Value::Decimal(read_lps(stream)?),
+        })
+    }
+}
+
+/// The ticks of a `DateTime` are stored in the lower 62 bits, its `DateTimeKind` in the upper two.
+fn date_time(bits: u64) -> Value {
+    let kind = match bits >> 62 {
+        0 => DateTimeKind::Unspecified,
+        1 => DateTimeKind::Utc,
+        // 3 marks a local time in the ambiguous hour of a DST transition.
+        _ => DateTimeKind::Local,
+    };
+    Value::DateTime((bits & 0x3fff_ffff_ffff_ffff) as i64, kind)
 }
 
+/// The highest rank of an array which .NET supports.
+const MAX_ARRAY_RANK: i32 = 32;
+
 #[derive(PartialEq, Eq, Debug, FromPrimitive)]
 enum BinaryArrayType {
     /// A single-dimensional Array.
@@ -127,6 +252,108 @@ enum BinaryArrayType {
     RectangularOffset = 5,
 }
 
+/// The `MessageFlags` bitfield of `BinaryMethodCall` and `BinaryMethodReturn` records. The flags
+/// dictate which of the optional fields follow in the record.
+#[derive(Debug, Clone, Copy)]
+struct MessageFlags(i32);
+
+impl MessageFlags {
+    const ARGS_INLINE: i32 = 0x2;
+    const ARGS_IS_ARRAY: i32 = 0x4;
+    const ARGS_IN_ARRAY: i32 = 0x8;
+    const CONTEXT_INLINE: i32 = 0x20;
+    const CONTEXT_IN_ARRAY: i32 = 0x40;
+    const METHOD_SIGNATURE_IN_ARRAY: i32 = 0x80;
+    const PROPERTIES_IN_ARRAY: i32 = 0x100;
+    const RETURN_VALUE_INLINE: i32 = 0x800;
+    const RETURN_VALUE_IN_ARRAY: i32 = 0x1000;
+    const EXCEPTION_IN_ARRAY: i32 = 0x2000;
+    const GENERIC_METHOD: i32 = 0x8000;
+
+    /// The flags of values stored in the call array record following the message.
+    const IN_ARRAY: i32 = Self::ARGS_IS_ARRAY
+        | Self::ARGS_IN_ARRAY
+        | Self::CONTEXT_IN_ARRAY
+        | Self::METHOD_SIGNATURE_IN_ARRAY
+        | Self::PROPERTIES_IN_ARRAY
+        | Self::RETURN_VALUE_IN_ARRAY
+        | Self::EXCEPTION_IN_ARRAY
+        | Self::GENERIC_METHOD;
+
+    fn contains(&self, flag: i32) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+impl FromStream for MessageFlags {
+    fn from_stream<R: io::Read>(stream: &mut R) -> Result<Self, ParseError> {
+        Ok(MessageFlags(read_i32(stream)?))
+    }
+}
+
+/// Reads a `ValueWithCode`, a primitive value prefixed by its `PrimitiveType`.
+fn read_value_with_code<R: io::Read>(stream: &mut R) -> Result<Value, ParseError> {
+    PrimitiveType::from_stream(stream)?.read(stream)
+}
+
+/// Reads the length of an array, which must not be negative.
+fn read_array_length<R: io::Read>(stream: &mut R) -> Result<usize, ParseError> {
+    let length = read_i32(stream)?;
+    usize::try_from(length).map_err(|_| ParseErrorKind::InvalidArrayLength { length }.into())
+}
+
+/// Reads a `StringValueWithCode`, a length prefixed string prefixed by `PrimitiveType::String`.
+fn read_string_value_with_code<R: io::Read>(stream: &mut R) -> Result<String, ParseError> {
+    let primitive_type = PrimitiveType::from_stream(stream)?;
+    match primitive_type {
+        PrimitiveType::String => read_lps(stream),
+        found => Err(ParseErrorKind::UnexpectedPrimitive {
+            expected: PrimitiveType::String,
+            found,
+        }
+        .into()),
+    }
+}
+
+/// The approximate size in bytes of `value`, not including the values it contains. The names of
+/// objects are shared with their class, so they are not counted.
+fn shallow_size(value: &Value) -> usize {
+    let heap_size = match value {
+        Value::String(string) | Value::Decimal(string) => string.len(),
+        Value::Bytes(bytes) => bytes.len(),
+        Value::Array(lengths, lower_bounds, values) => {
+            lengths.len() * core::mem::size_of::<usize>()
+                + lower_bounds.len() * core::mem::size_of::<i32>()
+                + values.len() * core::mem::size_of::<Value>()
+        }
+        Value::Object(_, _, members) => members.len() * core::mem::size_of::<(Name, Value)>(),
+        _ => 0,
+    };
+    core::mem::size_of::<Value>() + heap_size
+}
+
+/// Whether `value` is a `MethodCall` or `MethodReturn`, which is the root of its message.
+fn is_method_message(value: &Value) -> bool {
+    matches!(value, Value::MethodCall { .. } | Value::MethodReturn { .. })
+}
+
+/// Reads the call context of a method message, if the flags mark it as inline.
+fn read_call_context<R: io::Read>(
+    stream: &mut R,
+    flags: MessageFlags,
+) -> Result<Option<Box<Value>>, ParseError> {
+    flags
+        .contains(MessageFlags::CONTEXT_INLINE)
+        .then(|| read_string_value_with_code(stream).map(|id| Box::new(Value::String(id))))
+        .transpose()
+}
+
+/// Reads an `ArrayOfValueWithCode`.
+fn read_array_of_value_with_code<R: io::Read>(stream: &mut R) -> Result<Vec<Value>, ParseError> {
+    let length = read_i32(stream)?;
+    (0..length).map(|_| read_value_with_code(stream)).collect()
+}
+
 struct ClassInfo {
     id: i32,
     name: String,
@@ -134,31 +361,36 @@ struct ClassInfo {
 }
 
 impl FromStream for ClassInfo {
-    fn from_stream<R: io::Read>(stream: &mut R) -> Self {
-        let id = read_i32(stream);
-        let name = read_lps(stream);
-        let member_count = read_i32(stream);
-        let member_names = (0..member_count).map(|_| read_lps(stream)).collect();
-        Self {
+    fn from_stream<R: io::Read>(stream: &mut R) -> Result<Self, ParseError> {
+        let id = read_i32(stream)?;
+        let name = read_lps(stream)?;
+        let member_count = read_i32(stream)?;
+        if member_count < 0 {
+            return Err(ParseErrorKind::InvalidMemberCount { member_count }.into());
+        }
+        let member_names = (0..member_count)
+            .map(|_| read_lps(stream))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
             id,
             name,
             field_names: member_names,
-        }
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 struct ClassTypeInfo {
-    _name: String,
+    name: String,
     _library_id: i32,
 }
 
 impl FromStream for ClassTypeInfo {
-    fn from_stream<R: io::Read>(stream: &mut R) -> Self {
-        Self {
-            _name: read_lps(stream),
-            _library_id: read_i32(stream),
-        }
+    fn from_stream<R: io::Read>(stream: &mut R) -> Result<Self, ParseError> {
+        Ok(Self {
+            name: read_lps(stream)?,
+            _library_id: read_i32(stream)?,
+        })
     }
 }
 
@@ -171,322 +403,1313 @@ enum AdditionalInfos {
 }
 
 impl AdditionalInfos {
-    fn from_stream<R: io::Read>(stream: &mut R, binary_type: BinaryType) -> Self {
-        match binary_type {
+    fn from_stream<R: io::Read>(
+        stream: &mut R,
+        binary_type: BinaryType,
+    ) -> Result<Self, ParseError> {
+        Ok(match binary_type {
             BinaryType::Primitive | BinaryType::PrimitiveArray => {
-                AdditionalInfos::PrimitiveType(PrimitiveType::from_stream(stream))
+                AdditionalInfos::PrimitiveType(PrimitiveType::from_stream(stream)?)
             }
-            BinaryType::SystemClass => AdditionalInfos::ClassName(read_lps(stream)),
-            BinaryType::Class => AdditionalInfos::Class(ClassTypeInfo::from_stream(stream)),
+            BinaryType::SystemClass => AdditionalInfos::ClassName(read_lps(stream)?),
+            BinaryType::Class => AdditionalInfos::Class(ClassTypeInfo::from_stream(stream)?),
             _ => AdditionalInfos::Nothing,
-        }
+        })
     }
 }
 
+/// A member of a class: its name and, if the class record declares member types, its type.
+/// Members of `ClassWithMembers` and `SystemClassWithMembers` have no type, their values are
+/// always stored as separate records.
 #[derive(Debug, Clone)]
-pub struct ClassField(String, BinaryType, AdditionalInfos);
+pub struct ClassField(Name, Option<(BinaryType, AdditionalInfos)>);
+
+impl ClassField {
+    /// A member without type information, its value is stored as a separate record. This is
+    /// the case for all members which are not primitives, regardless of their declared type.
+    pub fn untyped(name: impl Into<Name>) -> Self {
+        ClassField(name.into(), None)
+    }
+
+    /// A member of a primitive type, its value is stored inline.
+    pub fn primitive(name: impl Into<Name>, primitive_type: PrimitiveType) -> Self {
+        let member_type = (
+            BinaryType::Primitive,
+            AdditionalInfos::PrimitiveType(primitive_type),
+        );
+        ClassField(name.into(), Some(member_type))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The declared type of the member, `None` for untyped members.
+    pub fn binary_type(&self) -> Option<BinaryType> {
+        self.1.as_ref().map(|(binary_type, _)| *binary_type)
+    }
+
+    /// The type of a `Primitive` member, or of the elements of a `PrimitiveArray` member.
+    pub fn primitive_type(&self) -> Option<PrimitiveType> {
+        match &self.1 {
+            Some((_, AdditionalInfos::PrimitiveType(primitive_type))) => Some(*primitive_type),
+            _ => None,
+        }
+    }
+
+    /// The class name of a `SystemClass` or `Class` member.
+    pub fn class_name(&self) -> Option<&str> {
+        match &self.1 {
+            Some((_, AdditionalInfos::ClassName(name))) => Some(name),
+            Some((_, AdditionalInfos::Class(ClassTypeInfo { name, .. }))) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// A class definition: its name, the name of the library defining it (`None` for system classes)
+/// and its fields.
 #[derive(Debug, Clone)]
-pub struct Class(String, Vec<ClassField>);
+pub struct Class(Name, Option<Name>, Vec<ClassField>);
+
+impl Class {
+    pub fn new(
+        name: impl Into<Name>,
+        library_name: Option<String>,
+        fields: Vec<ClassField>,
+    ) -> Self {
+        Class(name.into(), library_name.map(Name::from), fields)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// The name of the library defining the class, `None` for system classes.
+    pub fn library_name(&self) -> Option<&str> {
+        self.1.as_deref()
+    }
+
+    /// The fields in the order their values are serialized. The members of a `Value::Object`
+    /// of the class are keyed by the names of these fields.
+    pub fn fields(&self) -> &[ClassField] {
+        &self.2
+    }
+}
 
 struct DecoderState<'a, R: io::Read> {
-    stream: &'a mut R,
+    stream: CountingReader<'a, R>,
+    options: ParseOptions,
 
     root_id: Option<i32>,
     header_id: Option<i32>,
+    major_version: Option<i32>,
+    minor_version: Option<i32>,
 
-    libraries: HashMap<i32, String>,
-    classes: HashMap<i32, Class>,
-    values: HashMap<i32, Value>,
+    libraries: Map<i32, Name>,
+    // Classes are shared, so that instantiating one does not copy its definition.
+    classes: Map<i32, Rc<Class>>,
+    /// The class, library and member names read so far, see `ParseOptions::intern_strings`.
+    names: BTreeSet<Name>,
+    values: Map<i32, Value>,
+    message_end: bool,
 
-    // This is a bit of a hack. NRBF encodes sequences of nulls as either NullMultiple or
-    // NullMultiple256. The problem is that a record can "contain" multiple values in sequence. To
-    // unpack this, we use `null_count`, which is used to emit Null values instead of reading more
-    // records, when a null multiple has been encountered.
+    // NRBF encodes sequences of nulls as either NullMultiple or NullMultiple256, so a single
+    // record can stand for multiple values in sequence. To unpack this, `null_count` is used to
+    // emit Null values instead of reading more records. A run belongs to the array or object
+    // whose elements are being read, see `scoped_nulls`.
     null_count: usize,
+
+    /// The nesting depth of the record or reference being decoded, see `ParseOptions::max_depth`.
+    depth: usize,
+    /// The number of values decoded so far, see `ParseOptions::max_values`.
+    value_count: usize,
+    /// The approximate size in bytes of the values produced by resolving references so far.
+    resolved_size: usize,
+    /// The approximate size in bytes of each object once resolved, by object id. Further
+    /// references to the object copy it, see `ParseOptions::max_copied_bytes`.
+    resolved_sizes: Map<i32, usize>,
+    /// The approximate size in bytes of the copies made so far.
+    copied_bytes: usize,
+    /// Set while copying an object, whose size has been accounted for as a whole.
+    copying: bool,
+
+    /// Set when `UnknownPolicy::SkipRemaining` stopped decoding, after which no more records are
+    /// read.
+    skipping_remaining: bool,
+    /// Set by `parse_nrbf_lenient`, to stop decoding at any error like at an unknown record.
+    lenient: bool,
+    /// The error that stopped decoding in lenient mode.
+    lenient_error: Option<ParseError>,
+
+    /// The number of records decoded so far, including nested ones.
+    records_read: u64,
+    /// The ids of the objects defined so far, in order, if requested by the `RecordReader`.
+    defined: Option<Vec<i32>>,
+    /// The type of the record that defined the root object, once it has been read.
+    root_record_type: Option<RecordType>,
+    /// The first top-level value without object id, which is the root if no record defines it.
+    loose_value: Option<Value>,
+    /// The flags of the method message and the id of its call array, whose values are moved into
+    /// the message by `finish_method`.
+    call_array: Option<(MessageFlags, i32)>,
+    warnings: Vec<ParseWarning>,
 }
 
 impl<'a, R: io::Read> DecoderState<'a, R> {
-    fn new(stream: &'a mut R) -> Self {
+    fn new(stream: &'a mut R, mut options: ParseOptions) -> Self {
+        let classes = core::mem::take(&mut options.known_classes)
+            .into_iter()
+            .map(|(id, class)| (id, Rc::new(class)))
+            .collect();
         DecoderState {
-            stream,
+            stream: CountingReader::new(stream),
+            options,
             root_id: Default::default(),
             header_id: Default::default(),
+            major_version: Default::default(),
+            minor_version: Default::default(),
             libraries: Default::default(),
-            classes: Default::default(),
+            classes,
+            names: Default::default(),
             values: Default::default(),
+            message_end: false,
 
             null_count: Default::default(),
+            depth: 0,
+            value_count: 0,
+            resolved_size: 0,
+            resolved_sizes: Default::default(),
+            copied_bytes: 0,
+            copying: false,
+
+            skipping_remaining: false,
+            lenient: false,
+            lenient_error: None,
+            records_read: 0,
+            defined: None,
+            root_record_type: None,
+            loose_value: None,
+            call_array: None,
+            warnings: Vec::new(),
         }
     }
 
-    fn parse_class_member(&mut self, class_field: &ClassField) -> (String, Value) {
-        let ClassField(field_name, binary_type, additional_infos) = class_field;
+    fn parse_class_member(
+        &mut self,
+        class_field: &ClassField,
+    ) -> Result<(Name, Value), ParseError> {
+        let ClassField(field_name, member_type) = class_field;
+        let Some((binary_type, additional_infos)) = member_type else {
+            return Ok((field_name.clone(), self.next_value_record()?));
+        };
         let value = match (binary_type, additional_infos) {
-            (BinaryType::Record, AdditionalInfos::Nothing) => self.next_value_record(),
+            (BinaryType::Primitive, AdditionalInfos::PrimitiveType(_))
+                if self.skipping_remaining =>
+            {
+                Value::Null
+            }
             (BinaryType::Primitive, AdditionalInfos::PrimitiveType(primitive_type)) => {
-                primitive_type.read(self.stream)
+                primitive_type.read(&mut self.stream)?
             }
-            (BinaryType::String, AdditionalInfos::Nothing) => self.next_value_record(),
+            (BinaryType::String, AdditionalInfos::Nothing) => self.next_value_record()?,
+            (BinaryType::Object, AdditionalInfos::Nothing) => self.next_value_record()?,
             (BinaryType::SystemClass, AdditionalInfos::ClassName(_system_class_name)) => {
-                self.next_value_record()
+                self.next_value_record()?
+            }
+            (BinaryType::Class, AdditionalInfos::Class(_class_type_info)) => {
+                self.next_value_record()?
             }
-            (BinaryType::Class, AdditionalInfos::Class(_)) => self.next_value_record(),
             (BinaryType::PrimitiveArray, AdditionalInfos::PrimitiveType(_primitive_type)) => {
-                self.next_value_record()
+                self.next_value_record()?
             }
-            _ => panic!("No parser for {binary_type:?}/{additional_infos:?} implemented"),
+            (BinaryType::ObjectArray, AdditionalInfos::Nothing) => self.next_value_record()?,
+            (BinaryType::StringArray, AdditionalInfos::Nothing) => self.next_value_record()?,
+            // `AdditionalInfos::from_stream` and the `ClassField` constructors only pair each
+            // binary type with the kind of additional info matched above.
+            _ => unreachable!("{binary_type:?} member with {additional_infos:?}"),
         };
 
-        (field_name.clone(), value)
+        Ok((field_name.clone(), value))
     }
 
-    fn parse_object(&mut self, class_id: i32) -> Value {
-        let Class(class_name, fields) = self
-            .classes
-            .get(&class_id)
-            .expect(&format!("Class {class_id} is not yet defined"))
-            .clone();
-        let members = fields
-            .iter()
-            .map(|class_field| self.parse_class_member(class_field))
-            .collect::<HashMap<_, _>>();
-        Value::Object(class_name.clone(), members)
+    fn parse_object(&mut self, class: Rc<Class>) -> Result<Value, ParseError> {
+        let Class(class_name, library_name, fields) = class.as_ref();
+        let members = self.scoped_nulls(|decoder| {
+            fields
+                .iter()
+                .map(|class_field| decoder.parse_class_member(class_field))
+                .collect::<Result<Map<_, _>, _>>()
+        })?;
+        Ok(Value::Object(
+            class_name.clone(),
+            library_name.clone(),
+            members,
+        ))
     }
 
-    fn next_value_record(&mut self) -> Value {
+    /// Read the elements of an array or object. Runs of nulls pending in the enclosing container
+    /// are set aside meanwhile, and runs started inside may not extend past its last element.
+    fn scoped_nulls<T>(
+        &mut self,
+        read_elements: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let outer_null_count = core::mem::take(&mut self.null_count);
+        let elements = read_elements(self)?;
         if self.null_count > 0 {
+            let excess = self.null_count;
+            return Err(ParseErrorKind::NullRunTooLong { excess }.into());
+        }
+        self.null_count = outer_null_count;
+        Ok(elements)
+    }
+
+    /// Read the `length` elements of an array of objects or strings, each of them a record. A
+    /// single `ObjectNullMultiple` record can stand for any number of them, so their number is
+    /// limited.
+    fn read_element_records(&mut self, length: usize) -> Result<Vec<Value>, ParseError> {
+        self.check_array_length(length)?;
+        self.count_values(length)?;
+        self.scoped_nulls(|decoder| (0..length).map(|_| decoder.next_value_record()).collect())
+    }
+
+    /// Read `length` consecutive primitives of the same type, as stored in primitive arrays.
+    fn read_primitives(
+        &mut self,
+        primitive: PrimitiveType,
+        length: usize,
+    ) -> Result<Vec<Value>, ParseError> {
+        if let Some(size) = primitive.size() {
+            // Read the whole block at once instead of dispatching per element.
+            let bytes = read_byte_array(&mut self.stream, length.saturating_mul(size))?;
+            let values = bytes
+                .chunks_exact(size)
+                .map(|bytes| primitive.decode(bytes));
+            return Ok(values.collect());
+        }
+        (0..length)
+            .map(|_| primitive.read(&mut self.stream))
+            .collect()
+    }
+
+    fn check_array_length(&self, length: usize) -> Result<(), ParseError> {
+        match self.options.max_array_length {
+            Some(limit) if length > limit => {
+                Err(ParseErrorKind::ArrayTooLong { length, limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Count `count` more values against `ParseOptions::max_values`.
+    fn count_values(&mut self, count: usize) -> Result<(), ParseError> {
+        self.value_count = self.value_count.saturating_add(count);
+        match self.options.max_values {
+            Some(limit) if self.value_count > limit => {
+                Err(ParseErrorKind::TooManyValues { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Account for a copy of `size` bytes against `ParseOptions::max_copied_bytes`.
+    fn charge_copy(&mut self, size: usize) -> Result<(), ParseError> {
+        self.copied_bytes = self.copied_bytes.saturating_add(size);
+        match self.options.max_copied_bytes {
+            Some(limit) if self.copied_bytes > limit => {
+                Err(ParseErrorKind::TooManyCopiedBytes { limit }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `f` one level deeper, failing if that exceeds `ParseOptions::max_depth`.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if let Some(limit) = self.options.max_depth {
+            if self.depth >= limit {
+                return Err(ParseErrorKind::TooDeep { limit }.into());
+            }
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Start a run of `count` nulls, returning the first of them. `next_value_record` only reads
+    /// a record once the previous run is used up, so no other run is pending.
+    fn null_run(&mut self, count: usize) -> Result<Value, ParseError> {
+        self.check_array_length(count)?;
+        self.null_count = count;
+        if self.options.preserve_null_runs && count > 0 {
             self.null_count -= 1;
-            return Value::Null;
+            return Ok(Value::NullRun(count));
         }
+        self.next_value_record()
+    }
 
-        match RecordType::from_stream(self.stream) {
-            // Non-value records.
-            RecordType::SerializationHeader => {
-                self.root_id = Some(read_i32(self.stream));
-                self.header_id = Some(read_i32(self.stream));
-                let major_version = read_i32(self.stream);
-                assert_eq!(major_version, 1, "Major version must be 1");
-                let minor_version = read_i32(self.stream);
-                assert_eq!(minor_version, 0, "Minor version must be 0");
-                Value::Bottom
+    /// Look up the name of the library with id `library_id`, which must have been declared in
+    /// strict mode.
+    fn library_name(&mut self, library_id: i32) -> Result<Option<Name>, ParseError> {
+        match self.libraries.get(&library_id) {
+            None if self.options.strict => {
+                Err(ParseErrorKind::UnknownLibrary { library_id }.into())
+            }
+            None => {
+                self.warn(ParseWarningKind::UnknownLibrary { library_id });
+                Ok(None)
+            }
+            name => Ok(name.cloned()),
+        }
+    }
+
+    /// Share `name` with earlier occurrences of the same name if `ParseOptions::intern_strings`
+    /// is set.
+    fn intern(&mut self, name: String) -> Name {
+        if !self.options.intern_strings {
+            return name.into();
+        }
+        if let Some(interned) = self.names.get(name.as_str()) {
+            return interned.clone();
+        }
+        let name = Name::from(name);
+        self.names.insert(name.clone());
+        name
+    }
+
+    fn warn(&mut self, kind: ParseWarningKind) {
+        let offset = self.stream.offset();
+        self.warnings.push(ParseWarning { kind, offset });
+    }
+
+    fn next_value_record(&mut self) -> Result<Value, ParseError> {
+        loop {
+            if self.null_count > 0 {
+                self.null_count -= 1;
+                return Ok(Value::Null);
+            }
+
+            if self.skipping_remaining {
+                return Ok(Value::Null);
+            }
+
+            let offset = self.stream.offset();
+            let record_type = match self.peek_record_type() {
+                Err(error) if self.skips_unknown(&error) => return Ok(self.skip_remaining(error)),
+                result => result?,
+            };
+            read_u8(&mut self.stream)?;
+            let value = match self.nested(|decoder| decoder.parse_record(record_type)) {
+                Err(error) if self.skips_unknown(&error) => return Ok(self.skip_remaining(error)),
+                result => result?,
+            };
+            self.records_read += 1;
+
+            // A `MemberReference` to the root refers to it, but does not define it. Method messages
+            // are the root, without an object id, even if another record has the root id.
+            let method_root = matches!(
+                self.root_record_type,
+                Some(RecordType::MethodCall | RecordType::MethodReturn)
+            );
+            let defines_root = record_type != RecordType::MemberReference
+                && !method_root
+                && matches!(value, Value::Reference(id) if Some(id) == self.root_id);
+            if defines_root || is_method_message(&value) {
+                self.root_record_type = Some(record_type);
+            }
+
+            if let Some(on_record) = self.options.on_record.as_mut() {
+                let object_id = match value {
+                    Value::Reference(id) => Some(id),
+                    _ => None,
+                };
+                on_record(&RecordTrace {
+                    record_type,
+                    object_id,
+                    offset,
+                });
+            }
+
+            // A library precedes the first class record referring to it, but is not a value itself.
+            // Libraries are skipped in a loop, as any number of them may follow each other.
+            if record_type == RecordType::BinaryLibrary {
+                continue;
+            }
+            return Ok(value);
+        }
+    }
+
+    /// Store the value of the record with object id `id`, returning a reference to it.
+    fn define(&mut self, id: i32, value: Value) -> Result<Value, ParseError> {
+        if self.values.contains_key(&id) {
+            return Err(ParseErrorKind::DuplicateObjectId { object_id: id }.into());
+        }
+        self.values.insert(id, value);
+        if let Some(defined) = self.defined.as_mut() {
+            defined.push(id);
+        }
+        Ok(Value::Reference(id))
+    }
+
+    /// Read a record which is not an element of an array or object. Nulls stand for nothing
+    /// there, so the rest of a run of them is dropped instead of being read one by one.
+    fn next_top_level_record(&mut self) -> Result<Value, ParseError> {
+        let value = self.next_value_record()?;
+        self.null_count = 0;
+        if self.loose_value.is_none() && !matches!(value, Value::Reference(_) | Value::Bottom) {
+            self.loose_value = Some(value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Read the call array record following a method message, if the message flags any of its
+    /// values as "in array".
+    fn read_call_array(&mut self, flags: MessageFlags) -> Result<(), ParseError> {
+        if !flags.contains(MessageFlags::IN_ARRAY) {
+            return Ok(());
+        }
+        match self.next_value_record()? {
+            Value::Reference(id) => {
+                self.call_array = Some((flags, id));
+                Ok(())
+            }
+            _ => Err(ParseErrorKind::InvalidCallArray.into()),
+        }
+    }
+
+    /// Move the values of the call array of a method message into its fields, once all records
+    /// have been read. The values are resolved with `resolve_references` if `stack` is given, and
+    /// kept unresolved otherwise.
+    fn finish_method(
+        &mut self,
+        mut message: Value,
+        stack: Option<&mut Vec<i32>>,
+    ) -> Result<Value, ParseError> {
+        let Some((flags, array_id)) = self.call_array.take() else {
+            return Ok(message);
+        };
+        let array = match stack {
+            Some(stack) => self.resolve_references(Value::Reference(array_id), stack)?,
+            None => self.values.get(&array_id).cloned().unwrap_or(Value::Null),
+        };
+        let elements = match array {
+            Value::Array(_, _, elements) => elements,
+            // The call array was not decoded, see `UnknownPolicy::SkipRemaining`.
+            _ if self.skipping_remaining => return Ok(message),
+            _ => return Err(ParseErrorKind::InvalidCallArray.into()),
+        };
+
+        let (return_value, call_context, args) = match &mut message {
+            Value::MethodCall {
+                call_context, args, ..
+            } => (None, call_context, args),
+            Value::MethodReturn {
+                return_value,
+                call_context,
+                args,
+            } => (Some(return_value), call_context, args),
+            _ => return Ok(message),
+        };
+        if flags.contains(MessageFlags::ARGS_IS_ARRAY) {
+            *args = Some(elements);
+            return Ok(message);
+        }
+
+        // The values are stored in the order of the flags below, each present if flagged.
+        let mut elements = elements.into_iter();
+        let mut next = || elements.next().ok_or(ParseErrorKind::InvalidCallArray);
+        let mut ignored = 0;
+        let ignored_flags = match return_value {
+            None => [
+                MessageFlags::GENERIC_METHOD,
+                MessageFlags::METHOD_SIGNATURE_IN_ARRAY,
+            ],
+            Some(_) => [MessageFlags::EXCEPTION_IN_ARRAY, 0],
+        };
+        if let Some(return_value) = return_value {
+            if flags.contains(MessageFlags::RETURN_VALUE_IN_ARRAY) {
+                *return_value = Some(Box::new(next()?));
+            }
+        }
+        if flags.contains(MessageFlags::ARGS_IN_ARRAY) {
+            // The arguments are an array of their own, a reference to it if unresolved.
+            let values = match next()? {
+                Value::Reference(id) => self.values.get(&id).cloned(),
+                Value::Referenced(_, value) => Some(*value),
+                value => Some(value),
+            };
+            match values {
+                Some(Value::Array(_, _, values)) => *args = Some(values),
+                _ => return Err(ParseErrorKind::InvalidCallArray.into()),
+            }
+        }
+        for flag in ignored_flags {
+            if flags.contains(flag) {
+                next()?;
+                ignored += 1;
             }
+        }
+        if flags.contains(MessageFlags::CONTEXT_IN_ARRAY) {
+            *call_context = Some(Box::new(next()?));
+        }
+        if flags.contains(MessageFlags::PROPERTIES_IN_ARRAY) {
+            next()?;
+            ignored += 1;
+        }
+        if ignored > 0 {
+            self.warn(ParseWarningKind::IgnoredCallArrayValues { count: ignored });
+        }
+        Ok(message)
+    }
+
+    fn skips_unknown(&self, error: &ParseError) -> bool {
+        if self.lenient {
+            return true;
+        }
+        self.options.on_unknown_record == UnknownPolicy::SkipRemaining
+            && matches!(
+                error.kind,
+                ParseErrorKind::UnexpectedEnumValue {
+                    enum_name: "RecordType",
+                    ..
+                } | ParseErrorKind::UnsupportedRecord { .. }
+            )
+    }
+
+    /// Stop decoding because of `error`, as if the message ended here.
+    fn skip_remaining(&mut self, error: ParseError) -> Value {
+        let reason = format!("{}", error.kind);
+        self.warn(ParseWarningKind::SkippedRemaining { reason });
+        if self.lenient {
+            self.lenient_error = Some(self.stream.locate(error));
+        }
+        self.skipping_remaining = true;
+        self.message_end = true;
+        self.null_count = 0;
+        Value::Null
+    }
+
+    /// The type of the next record, without consuming it. An unknown record type is reported at
+    /// the offset of the offending byte.
+    fn peek_record_type(&mut self) -> Result<RecordType, ParseError> {
+        enum_from_u8(peek_u8(&mut self.stream)?)
+    }
+
+    fn parse_record(&mut self, record_type: RecordType) -> Result<Value, ParseError> {
+        // Nested records are decoded recursively through this function. Larger records are read
+        // by separate functions, so that its stack frame stays small in debug builds too.
+        match record_type {
+            // Non-value records.
+            RecordType::SerializationHeader => self.parse_header(),
             RecordType::BinaryLibrary => {
-                let id = read_i32(self.stream);
-                let name = read_lps(self.stream);
+                let id = read_i32(&mut self.stream)?;
+                let name = read_lps(&mut self.stream)?;
+                let name = self.intern(name);
                 self.libraries.insert(id, name);
-                Value::Bottom
+                Ok(Value::Bottom)
             }
-            RecordType::MessageEnd => Value::Bottom,
-            // Classes.
-            RecordType::ClassWithId => {
-                // New instance of a class, creates new object id, reuses previous class id.
-                let id = read_i32(self.stream);
-
-                // An INT32 value (as specified in [MS-DTYP] section 2.2.22) that references one
-                // of the other Class records by its ObjectId. A SystemClassWithMembers,
-                // SystemClassWithMembersAndTypes, ClassWithMembers, or ClassWithMembersAndTypes
-                // record with the value of this field in its ObjectId field MUST appear earlier
-                // in the serialization stream.
-                let class_id = read_i32(self.stream);
-                let object = self.parse_object(class_id);
-
-                self.values.insert(id, object);
-                Value::Reference(id)
-            }
-            RecordType::ClassWithMembers => {
-                // New instance of a NEW class, TODO has no object id, creates class id.
-                // Holds member names, types not needed, they are records.
-                let ClassInfo {
-                    id,
-                    name: class_name,
-                    field_names,
-                } = ClassInfo::from_stream(self.stream);
-                let _library_id = read_i32(self.stream);
-
-                let class_fields = field_names
-                    .iter()
-                    .map(|name| {
-                        ClassField(name.clone(), BinaryType::Record, AdditionalInfos::Nothing)
-                    })
-                    .collect();
-
-                let class = Class(class_name, class_fields);
-                self.classes.insert(id, class);
-
-                let object = tee(self.parse_object(id));
-
-                self.values.insert(id, object);
-                Value::Reference(id)
-            }
-            RecordType::ClassWithMembersAndTypes => {
-                // New instance of a NEW class.
-                let ClassInfo {
-                    id,
-                    name: class_name,
-                    field_names,
-                } = ClassInfo::from_stream(self.stream);
-                let binary_types = field_names
-                    .iter()
-                    .map(|_| BinaryType::from_stream(self.stream))
-                    .collect::<Vec<_>>();
-                let additional_infos = binary_types
-                    .iter()
-                    .cloned()
-                    .map(|binary_type| AdditionalInfos::from_stream(self.stream, binary_type))
-                    .collect::<Vec<_>>();
-                let _library_id = read_i32(self.stream);
-
-                let class_fields = field_names
-                    .iter()
-                    .zip(binary_types.into_iter())
-                    .zip(additional_infos.into_iter())
-                    .map(|((name, binary_type), additional_infos)| {
-                        ClassField(name.clone(), binary_type, additional_infos)
-                    })
-                    .collect();
-
-                let class = Class(class_name, class_fields);
-                self.classes.insert(id, class);
-
-                let object = tee(self.parse_object(id));
-
-                self.values.insert(id, object);
-                Value::Reference(id)
+            RecordType::MessageEnd => {
+                self.message_end = true;
+                Ok(Value::Bottom)
             }
+            // Classes.
+            RecordType::ClassWithId => self.parse_class_with_id(),
+            RecordType::ClassWithMembers => self.parse_class_with_members(),
+            RecordType::SystemClassWithMembers => self.parse_system_class_with_members(),
+            RecordType::ClassWithMembersAndTypes => self.parse_class_with_members_and_types(),
             RecordType::SystemClassWithMembersAndTypes => {
-                // New instance of a NEW system (std) class, TODO has no object id, creates class id.
-                let ClassInfo {
-                    id,
-                    name: class_name,
-                    field_names,
-                } = ClassInfo::from_stream(self.stream);
-                let binary_types = field_names
-                    .iter()
-                    .map(|_| BinaryType::from_stream(self.stream))
-                    .collect::<Vec<_>>();
-                let additional_infos = binary_types
-                    .iter()
-                    .cloned()
-                    .map(|binary_type| AdditionalInfos::from_stream(self.stream, binary_type))
-                    .collect::<Vec<_>>();
-
-                let class_fields = field_names
-                    .iter()
-                    .zip(binary_types.into_iter())
-                    .zip(additional_infos.into_iter())
-                    .map(|((name, binary_type), additional_infos)| {
-                        ClassField(name.clone(), binary_type, additional_infos)
-                    })
-                    .collect();
-
-                let class = Class(class_name, class_fields);
-                self.classes.insert(id, class);
-
-                let object = tee(self.parse_object(id));
-
-                self.values.insert(id, object);
-                Value::Reference(id)
+                self.parse_system_class_with_members_and_types()
             }
             // Arrays.
-            RecordType::BinaryArray => {
-                let object_id = read_i32(self.stream);
-                let array_type = BinaryArrayType::from_stream(self.stream);
-                let rank = read_i32(self.stream);
-                let lengths = (0..rank)
-                    .map(|_| read_i32(self.stream) as usize)
-                    .collect::<Vec<_>>();
-                let lower_bounds = if array_type == BinaryArrayType::SingleOffset
-                    || array_type == BinaryArrayType::JaggedOffset
-                    || array_type == BinaryArrayType::RectangularOffset
-                {
-                    (0..rank).map(|_| read_i32(self.stream) as usize).collect()
-                } else {
-                    vec![0; rank.try_into().unwrap()]
-                };
-                let item_type = BinaryType::from_stream(self.stream);
-                let _additional_info = AdditionalInfos::from_stream(self.stream, item_type);
-
-                let size = lengths.iter().fold(1, |x, y| x * y);
-                let values = (0..size).map(|_| self.next_value_record()).collect();
-                self.values
-                    .insert(object_id, Value::Array(lengths, lower_bounds, values));
-                Value::Reference(object_id)
-            }
-            RecordType::ArraySinglePrimitive => {
-                let object_id = read_i32(self.stream);
-                let length = read_i32(self.stream) as usize;
-                let primitive = PrimitiveType::from_stream(self.stream);
-                let values = (0..length).map(|_| primitive.read(self.stream)).collect();
-                self.values
-                    .insert(object_id, Value::Array(vec![length], vec![0], values));
-                Value::Reference(object_id)
+            RecordType::BinaryArray => self.parse_binary_array(),
+            RecordType::ArraySinglePrimitive => self.parse_array_single_primitive(),
+            RecordType::ArraySingleObject | RecordType::ArraySingleString => {
+                self.parse_array_single()
             }
             RecordType::BinaryObjectString => {
-                let id = read_i32(self.stream);
-                let value = read_lps(self.stream);
-                self.values.insert(id, tee(Value::String(value)));
-                Value::Reference(id)
+                let id = read_i32(&mut self.stream)?;
+                let value = read_lps(&mut self.stream)?;
+                self.define(id, tee(Value::String(value)))
             }
             // Null sequences.
-            RecordType::ObjectNull => Value::Null,
+            RecordType::ObjectNull => Ok(Value::Null),
             RecordType::ObjectNullMultiple256 => {
-                assert_eq!(self.null_count, 0);
-                self.null_count = read_u8(self.stream) as usize;
-                self.next_value_record()
+                let count = read_u8(&mut self.stream)? as usize;
+                self.null_run(count)
             }
             RecordType::ObjectNullMultiple => {
-                assert_eq!(self.null_count, 0);
-                self.null_count = read_i32(self.stream) as usize;
-                self.next_value_record()
+                let count = read_i32(&mut self.stream)?;
+                let count = usize::try_from(count)
+                    .map_err(|_| ParseErrorKind::InvalidNullCount { count })?;
+                self.null_run(count)
             }
+            // Remoting messages.
+            RecordType::MethodCall => self.parse_method_call(),
+            RecordType::MethodReturn => self.parse_method_return(),
             // Other.
-            // RecordType::MemberPrimitiveTyped            => Record::MemberPrimitiveTyped(MemberPrimitiveTyped::from_stream(stream)),
-            RecordType::MemberReference => Value::Reference(read_i32(self.stream)),
-            // self.values
-            //     .remove(&id)
-            //     .expect("Reference was either already used or never defined.")
-            other => panic!("Unhandled record type: {other:?}"),
+            // A primitive in place of an object, e.g. a boxed value in an `object` member.
+            RecordType::MemberPrimitiveTyped => read_value_with_code(&mut self.stream),
+            RecordType::MemberReference => {
+                let id = read_i32(&mut self.stream)?;
+                Ok(match self.values.get(&id) {
+                    Some(value) if self.options.eager_resolve_known_refs => value.clone(),
+                    _ => Value::Reference(id),
+                })
+            }
+        }
+    }
+
+    /// Read a `SerializationHeader`, which sets the root id of the message.
+    fn parse_header(&mut self) -> Result<Value, ParseError> {
+        let mut read_field = || match read_i32(&mut self.stream) {
+            Err(ParseError {
+                kind: ParseErrorKind::Io(error),
+                ..
+            }) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(ParseErrorKind::TruncatedHeader.into())
+            }
+            result => result,
+        };
+        let root_id = read_field()?;
+        let header_id = read_field()?;
+        let major = read_field()?;
+        let minor = read_field()?;
+        if (major, minor) != (1, 0) {
+            if self.options.strict_version {
+                return Err(ParseErrorKind::UnsupportedVersion { major, minor }.into());
+            }
+            self.warn(ParseWarningKind::UnsupportedVersion { major, minor });
         }
+        let use_header_id = self.options.root_from_header_id && root_id == 0;
+        self.root_id = Some(if use_header_id { header_id } else { root_id });
+        self.header_id = Some(header_id);
+        self.major_version = Some(major);
+        self.minor_version = Some(minor);
+        Ok(Value::Bottom)
+    }
+
+    /// Read an instance of a class defined earlier in the message.
+    fn parse_class_with_id(&mut self) -> Result<Value, ParseError> {
+        // New instance of a class, creates new object id, reuses previous class id.
+        // The record type has already been read.
+        let referenced_by_offset = self.stream.offset() - 1;
+        let id = read_i32(&mut self.stream)?;
+
+        // An INT32 value (as specified in [MS-DTYP] section 2.2.22) that references one
+        // of the other Class records by its ObjectId. A SystemClassWithMembers,
+        // SystemClassWithMembersAndTypes, ClassWithMembers, or ClassWithMembersAndTypes
+        // record with the value of this field in its ObjectId field MUST appear earlier
+        // in the serialization stream.
+        let class_id = read_i32(&mut self.stream)?;
+        let class = match self.classes.get(&class_id) {
+            Some(class) => class.clone(),
+            // Instances share the object ids of class definitions, but are no classes.
+            None if self.values.contains_key(&class_id) => {
+                return Err(ParseErrorKind::ClassWithIdTargetNotAClass {
+                    object_id: class_id,
+                    referenced_by_offset,
+                }
+                .into())
+            }
+            None => {
+                return Err(ParseErrorKind::ClassNotDefined {
+                    class_id,
+                    referenced_by_offset,
+                }
+                .into())
+            }
+        };
+        let object = self.parse_object(class)?;
+
+        self.define(id, object)
+    }
+
+    fn parse_class_with_members(&mut self) -> Result<Value, ParseError> {
+        // New instance of a NEW class, TODO has no object id, creates class id.
+        // Holds member names, types not needed, they are records.
+        let ClassInfo {
+            id,
+            name: class_name,
+            field_names,
+        } = ClassInfo::from_stream(&mut self.stream)?;
+        let class_name = self.intern(class_name);
+        let library_id = read_i32(&mut self.stream)?;
+
+        let class_fields = field_names
+            .into_iter()
+            .map(|name| ClassField(self.intern(name), None))
+            .collect();
+
+        let library_name = self.library_name(library_id)?;
+        let class = Class(class_name, library_name, class_fields);
+        let class = Rc::new(class);
+        self.classes.insert(id, class.clone());
+
+        let object = tee(self.parse_object(class)?);
+
+        self.define(id, object)
     }
 
-    fn resolve_references(&mut self, v: Value) -> Value {
-        match v {
-            Value::Object(class, members) => Value::Object(
+    fn parse_system_class_with_members(&mut self) -> Result<Value, ParseError> {
+        // New instance of a NEW system (std) class, creates class id.
+        // Like `ClassWithMembers`, but without library id.
+        let ClassInfo {
+            id,
+            name: class_name,
+            field_names,
+        } = ClassInfo::from_stream(&mut self.stream)?;
+        let class_name = self.intern(class_name);
+
+        let class_fields = field_names
+            .into_iter()
+            .map(|name| ClassField(self.intern(name), None))
+            .collect();
+
+        let class = Class(class_name, None, class_fields);
+        let class = Rc::new(class);
+        self.classes.insert(id, class.clone());
+
+        let object = tee(self.parse_object(class)?);
+
+        self.define(id, object)
+    }
+
+    fn parse_class_with_members_and_types(&mut self) -> Result<Value, ParseError> {
+        // New instance of a NEW class.
+        let ClassInfo {
+            id,
+            name: class_name,
+            field_names,
+        } = ClassInfo::from_stream(&mut self.stream)?;
+        let class_name = self.intern(class_name);
+        let binary_types = field_names
+            .iter()
+            .map(|_| BinaryType::from_stream(&mut self.stream))
+            .collect::<Result<Vec<_>, _>>()?;
+        let additional_infos = binary_types
+            .iter()
+            .cloned()
+            .map(|binary_type| AdditionalInfos::from_stream(&mut self.stream, binary_type))
+            .collect::<Result<Vec<_>, _>>()?;
+        let library_id = read_i32(&mut self.stream)?;
+
+        let class_fields = field_names
+            .into_iter()
+            .zip(binary_types)
+            .zip(additional_infos)
+            .map(|((name, binary_type), additional_infos)| {
+                ClassField(self.intern(name), Some((binary_type, additional_infos)))
+            })
+            .collect();
+
+        let library_name = self.library_name(library_id)?;
+        let class = Class(class_name, library_name, class_fields);
+        let class = Rc::new(class);
+        self.classes.insert(id, class.clone());
+
+        let object = tee(self.parse_object(class)?);
+
+        self.define(id, object)
+    }
+
+    fn parse_system_class_with_members_and_types(&mut self) -> Result<Value, ParseError> {
+        // New instance of a NEW system (std) class, TODO has no object id, creates class id.
+        let ClassInfo {
+            id,
+            name: class_name,
+            field_names,
+        } = ClassInfo::from_stream(&mut self.stream)?;
+        let class_name = self.intern(class_name);
+        let binary_types = field_names
+            .iter()
+            .map(|_| BinaryType::from_stream(&mut self.stream))
+            .collect::<Result<Vec<_>, _>>()?;
+        let additional_infos = binary_types
+            .iter()
+            .cloned()
+            .map(|binary_type| AdditionalInfos::from_stream(&mut self.stream, binary_type))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let class_fields = field_names
+            .into_iter()
+            .zip(binary_types)
+            .zip(additional_infos)
+            .map(|((name, binary_type), additional_infos)| {
+                ClassField(self.intern(name), Some((binary_type, additional_infos)))
+            })
+            .collect();
+
+        let class = Class(class_name, None, class_fields);
+        let class = Rc::new(class);
+        self.classes.insert(id, class.clone());
+
+        let object = tee(self.parse_object(class)?);
+
+        self.define(id, object)
+    }
+
+    fn parse_binary_array(&mut self) -> Result<Value, ParseError> {
+        let object_id = read_i32(&mut self.stream)?;
+        let array_type = BinaryArrayType::from_stream(&mut self.stream)?;
+        let rank = read_i32(&mut self.stream)?;
+        if !(1..=MAX_ARRAY_RANK).contains(&rank) {
+            return Err(ParseErrorKind::InvalidArrayRank { rank }.into());
+        }
+        let mut lengths = (0..rank)
+            .map(|_| read_array_length(&mut self.stream))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut lower_bounds = if array_type == BinaryArrayType::SingleOffset
+            || array_type == BinaryArrayType::JaggedOffset
+            || array_type == BinaryArrayType::RectangularOffset
+        {
+            (0..rank)
+                .map(|_| read_i32(&mut self.stream))
+                .collect::<Result<_, _>>()?
+        } else {
+            vec![0; lengths.len()]
+        };
+        let item_type = BinaryType::from_stream(&mut self.stream)?;
+        let is_primitive = matches!(item_type, BinaryType::Primitive);
+        let additional_info = AdditionalInfos::from_stream(&mut self.stream, item_type)?;
+
+        if array_type == BinaryArrayType::Jagged || array_type == BinaryArrayType::JaggedOffset {
+            // The elements of a jagged array are arrays of independent sizes, each of
+            // them is a separate record. The enclosing array is single-dimensional.
+            lengths.truncate(1);
+            lower_bounds.truncate(1);
+        }
+
+        let size = lengths
+            .iter()
+            .try_fold(1usize, |size, &length| size.checked_mul(length))
+            .ok_or_else(|| ParseErrorKind::ArraySizeOverflow {
+                lengths: lengths.clone(),
+            })?;
+        let values = match additional_info {
+            // Primitive elements are stored inline, without a record each.
+            AdditionalInfos::PrimitiveType(primitive) if is_primitive => {
+                self.read_primitives(primitive, size)?
+            }
+            _ => self.read_element_records(size)?,
+        };
+        self.define(object_id, Value::Array(lengths, lower_bounds, values))
+    }
+
+    fn parse_array_single_primitive(&mut self) -> Result<Value, ParseError> {
+        let object_id = read_i32(&mut self.stream)?;
+        let length = read_array_length(&mut self.stream)?;
+        let primitive = PrimitiveType::from_stream(&mut self.stream)?;
+        let value = if primitive == PrimitiveType::Byte {
+            // Avoid a `Value` per byte for (potentially large) binary blobs.
+            Value::Bytes(read_byte_array(&mut self.stream, length)?)
+        } else {
+            // Single primitive arrays cannot have a lower bound, those are written as
+            // `BinaryArray` with `SingleOffset` instead.
+            let values = self.read_primitives(primitive, length)?;
+            Value::Array(vec![length], vec![0], values)
+        };
+        self.define(object_id, value)
+    }
+
+    fn parse_array_single(&mut self) -> Result<Value, ParseError> {
+        // The elements are records, as in a `BinaryArray` of objects or strings.
+        let object_id = read_i32(&mut self.stream)?;
+        let length = read_array_length(&mut self.stream)?;
+        let values = self.read_element_records(length)?;
+        self.define(object_id, Value::Array(vec![length], vec![0], values))
+    }
+
+    fn parse_method_call(&mut self) -> Result<Value, ParseError> {
+        let flags = MessageFlags::from_stream(&mut self.stream)?;
+        let method_name = read_string_value_with_code(&mut self.stream)?;
+        let type_name = read_string_value_with_code(&mut self.stream)?;
+        // As in `MethodReturn` below, the optional fields are positional, and values
+        // flagged as "in array" are stored in the call array record following it.
+        let call_context = read_call_context(&mut self.stream, flags)?;
+        let args = flags
+            .contains(MessageFlags::ARGS_INLINE)
+            .then(|| read_array_of_value_with_code(&mut self.stream))
+            .transpose()?;
+        self.read_call_array(flags)?;
+
+        // Method messages have no object id, they are the root of their message.
+        Ok(Value::MethodCall {
+            method_name,
+            type_name,
+            call_context,
+            args,
+        })
+    }
+
+    fn parse_method_return(&mut self) -> Result<Value, ParseError> {
+        let flags = MessageFlags::from_stream(&mut self.stream)?;
+        // The optional fields are positional, their presence is dictated by the flags.
+        // Values flagged as "in array" are stored in the call array record instead.
+        let return_value = flags
+            .contains(MessageFlags::RETURN_VALUE_INLINE)
+            .then(|| read_value_with_code(&mut self.stream).map(Box::new))
+            .transpose()?;
+        let call_context = read_call_context(&mut self.stream, flags)?;
+        let args = flags
+            .contains(MessageFlags::ARGS_INLINE)
+            .then(|| read_array_of_value_with_code(&mut self.stream))
+            .transpose()?;
+        self.read_call_array(flags)?;
+
+        Ok(Value::MethodReturn {
+            return_value,
+            call_context,
+            args,
+        })
+    }
+
+    /// Replace all references in `v` by the values they refer to. `stack` holds the ids of the
+    /// objects being resolved.
+    fn resolve_references(&mut self, v: Value, stack: &mut Vec<i32>) -> Result<Value, ParseError> {
+        // A reference is replaced by the value it refers to, which is accounted for on its own.
+        if matches!(v, Value::Reference(_)) {
+            return self.resolve_value(v, stack);
+        }
+        self.resolved_size = self.resolved_size.saturating_add(shallow_size(&v));
+        self.nested(|decoder| decoder.resolve_value(v, stack))
+    }
+
+    fn resolve_value(&mut self, v: Value, stack: &mut Vec<i32>) -> Result<Value, ParseError> {
+        Ok(match v {
+            Value::Object(class, library, members) => Value::Object(
                 class,
+                library,
                 members
                     .into_iter()
-                    .map(|(k, v)| (k, self.resolve_references(v)))
-                    .collect(),
+                    .map(|(k, v)| Ok((k, self.resolve_references(v, stack)?)))
+                    .collect::<Result<_, ParseError>>()?,
             ),
             Value::Array(a, b, values) => Value::Array(
                 a,
                 b,
                 values
                     .into_iter()
-                    .map(|v| self.resolve_references(v))
-                    .collect(),
+                    .map(|v| self.resolve_references(v, stack))
+                    .collect::<Result<_, _>>()?,
             ),
             Value::Reference(id) => loop {
+                // A method message is the root, even if another record has the root id.
+                if Some(id) == self.root_id
+                    && self.loose_value.as_ref().is_some_and(is_method_message)
+                {
+                    while !self.message_end {
+                        self.next_top_level_record()?;
+                    }
+                    let message = self.loose_value.take().unwrap();
+                    return self.finish_method(message, Some(stack));
+                }
+                if stack.contains(&id) {
+                    return Err(ParseErrorKind::ReferenceCycle { object_id: id }.into());
+                }
                 if let Some(v) = self.values.get(&id) {
-                    return self.resolve_references(v.clone());
+                    let v = v.clone();
+                    // The first reference to an object moves it into place, any further one
+                    // copies it. Copies are accounted for before making them, as a small message
+                    // can stand for an exponential number of them.
+                    let copy_size = self.resolved_sizes.get(&id).copied();
+                    if let Some(size) = copy_size.filter(|_| !self.copying) {
+                        self.charge_copy(size)?;
+                    }
+                    let copying = self.copying;
+                    self.copying |= copy_size.is_some();
+                    let start_size = self.resolved_size;
+                    stack.push(id);
+                    let resolved = self.resolve_references(v, stack);
+                    stack.pop();
+                    self.copying = copying;
+                    let resolved = resolved?;
+                    self.resolved_sizes
+                        .entry(id)
+                        .or_insert(self.resolved_size - start_size);
+                    // The stack is empty when resolving the root.
+                    if self.options.keep_reference_ids && !stack.is_empty() {
+                        return Ok(Value::Referenced(id, Box::new(resolved)));
+                    }
+                    return Ok(resolved);
+                }
+                if self.skipping_remaining {
+                    return Ok(Value::Null);
+                }
+                if self.message_end {
+                    // A root without object id, e.g. a `MemberPrimitiveTyped` record.
+                    if Some(id) == self.root_id {
+                        if let Some(value) = self.loose_value.take() {
+                            return Ok(value);
+                        }
+                    }
+                    return Err(ParseErrorKind::ObjectNotDefined { object_id: id }.into());
                 }
-                self.next_value_record();
+                self.next_top_level_record()?;
             },
             other => other,
+        })
+    }
+
+    /// Like `resolve_references`, but also resolves the fields of a method message, which is
+    /// only ever the root. It is kept out of `resolve_value`, whose stack frame is part of every
+    /// level of nesting.
+    #[cfg(feature = "json")]
+    fn resolve_root(&mut self, root: Value) -> Result<Value, ParseError> {
+        let stack = &mut Vec::new();
+        Ok(match root {
+            Value::MethodCall {
+                method_name,
+                type_name,
+                call_context,
+                args,
+            } => Value::MethodCall {
+                method_name,
+                type_name,
+                call_context: self.resolve_boxed(call_context, stack)?,
+                args: self.resolve_args(args, stack)?,
+            },
+            Value::MethodReturn {
+                return_value,
+                call_context,
+                args,
+            } => Value::MethodReturn {
+                return_value: self.resolve_boxed(return_value, stack)?,
+                call_context: self.resolve_boxed(call_context, stack)?,
+                args: self.resolve_args(args, stack)?,
+            },
+            root => self.resolve_references(root, stack)?,
+        })
+    }
+
+    #[cfg(feature = "json")]
+    fn resolve_boxed(
+        &mut self,
+        v: Option<Box<Value>>,
+        stack: &mut Vec<i32>,
+    ) -> Result<Option<Box<Value>>, ParseError> {
+        v.map(|v| self.resolve_references(*v, stack).map(Box::new))
+            .transpose()
+    }
+
+    #[cfg(feature = "json")]
+    fn resolve_args(
+        &mut self,
+        args: Option<Vec<Value>>,
+        stack: &mut Vec<i32>,
+    ) -> Result<Option<Vec<Value>>, ParseError> {
+        args.map(|args| {
+            args.into_iter()
+                .map(|v| self.resolve_references(v, stack))
+                .collect()
+        })
+        .transpose()
+    }
+
+    /// Read records up to and including the `SerializationHeader`, returning the root id.
+    fn read_header(&mut self) -> Result<i32, ParseError> {
+        while self.root_id.is_none() {
+            if self.message_end {
+                return Err(ParseErrorKind::MissingSerializationHeader.into());
+            }
+            match self.next_top_level_record() {
+                Err(ParseError {
+                    kind: ParseErrorKind::Io(error),
+                    ..
+                }) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(ParseErrorKind::MissingSerializationHeader.into());
+                }
+                result => result?,
+            };
         }
+        Ok(self.root_id.unwrap())
+    }
+
+    fn parse(&mut self) -> Result<ParsedDocument, ParseError> {
+        let root_id = self.read_header()?;
+        let root = self.resolve_references(Value::Reference(root_id), &mut Vec::new())?;
+        while !self.message_end {
+            self.next_top_level_record()?;
+        }
+
+        Ok(ParsedDocument {
+            root,
+            root_id,
+            root_record_type: self.root_record_type,
+            header_id: self.header_id.unwrap(),
+            major_version: self.major_version.unwrap(),
+            minor_version: self.minor_version.unwrap(),
+            objects: core::mem::take(&mut self.values),
+            classes: self
+                .classes
+                .iter()
+                .map(|(id, class)| (*id, Class::clone(class)))
+                .collect(),
+            warnings: core::mem::take(&mut self.warnings),
+        })
+    }
+
+    /// Read all records of the message without resolving references. Returns the root, a
+    /// reference to it unless it has no object id, and the values by object id.
+    fn parse_unresolved(&mut self) -> Result<(Value, Map<i32, Value>), ParseError> {
+        let root_id = self.read_header()?;
+        while !self.message_end {
+            self.next_top_level_record()?;
+        }
+
+        let root = match self.loose_value.take() {
+            Some(value) if is_method_message(&value) || !self.values.contains_key(&root_id) => {
+                self.finish_method(value, None)?
+            }
+            _ => Value::Reference(root_id),
+        };
+        Ok((root, core::mem::take(&mut self.values)))
+    }
+}
+
+/// Whether `data` starts with a `SerializationHeader` record of version 1.0, i.e. looks like an
+/// NRBF message. Only the first 17 bytes are inspected, the rest of the message is not validated.
+pub fn is_nrbf(data: &[u8]) -> bool {
+    let Some((&record_type, header)) = data.split_first() else {
+        return false;
+    };
+    let version = |offset: usize| {
+        header
+            .get(offset..offset + 4)
+            .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    record_type == RecordType::SerializationHeader as u8
+        && version(8) == Some(1)
+        && version(12) == Some(0)
+}
+
+/// Parse an NRBF message. The stream is read up to and including the `MessageEnd` record, so
+/// that data following the message can be read from the stream afterwards.
+///
+/// The parser issues many small reads and does not buffer internally, since that would consume
+/// data past the end of the message. Wrap unbuffered readers such as `File` in a `BufReader`.
+pub fn parse_nrbf<R: io::Read>(stream: &mut R) -> Result<Value, ParseError> {
+    parse_nrbf_with_options(stream, ParseOptions::default())
+}
+
+/// Parse an NRBF message embedded at the start of `stream`, returning the root and the number
+/// of bytes the message occupies. The stream is left positioned right after the `MessageEnd`
+/// record. If parsing fails, the position of the stream is unspecified.
+pub fn parse_nrbf_prefix<R: io::Read>(stream: &mut R) -> Result<(Value, u64), ParseError> {
+    parse_nrbf_prefix_with_options(stream, ParseOptions::default())
+}
+
+pub fn parse_nrbf_prefix_with_options<R: io::Read>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> Result<(Value, u64), ParseError> {
+    let mut decoder = DecoderState::new(stream, options);
+    match decoder.parse() {
+        Ok(document) => Ok((document.root, decoder.stream.offset())),
+        Err(error) => Err(decoder.stream.locate(error)),
     }
 }
 
-pub fn parse_nrbf<R: io::Read>(stream: &mut R) -> Value {
-    let mut decoder = DecoderState::new(stream);
-    while decoder.root_id.is_none() {
-        decoder.next_value_record();
+/// Parse NRBF messages following each other in `stream` until it ends. Each message has its own
+/// object ids. The stream must end right after a `MessageEnd` record, or be empty; the offset of
+/// an error counts from the start of the stream.
+pub fn parse_nrbf_all<R: io::Read>(stream: &mut R) -> Result<Vec<Value>, ParseError> {
+    parse_nrbf_all_with_options(stream, ParseOptions::default())
+}
+
+/// Like `parse_nrbf_all`, decoding each message with `options`. The limits apply to each message
+/// on its own, and the known classes are defined in each of them.
+pub fn parse_nrbf_all_with_options<R: io::Read>(
+    stream: &mut R,
+    mut options: ParseOptions,
+) -> Result<Vec<Value>, ParseError> {
+    // The decoder takes the known classes, they are handed to every message from here.
+    let known_classes = core::mem::take(&mut options.known_classes);
+    let mut messages = Vec::new();
+    let mut start = 0;
+    loop {
+        options.known_classes = known_classes.clone();
+        let mut decoder = DecoderState::new(stream, options);
+        match decoder.parse() {
+            Ok(document) => {
+                messages.push(document.root);
+                start += decoder.stream.offset();
+            }
+            // Nothing at all has been read, the stream ended after the previous message.
+            Err(ParseError {
+                kind: ParseErrorKind::MissingSerializationHeader,
+                ..
+            }) if decoder.stream.offset() == 0 => return Ok(messages),
+            Err(error) => return Err(decoder.stream.locate(error).shifted(start)),
+        }
+        options = decoder.options;
     }
+}
+
+pub fn parse_nrbf_with_options<R: io::Read>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> Result<Value, ParseError> {
+    parse_nrbf_document(stream, options).map(|document| document.root)
+}
+
+/// Parse an NRBF message, returning its root along with the header metadata.
+pub fn parse_nrbf_document<R: io::Read>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> Result<ParsedDocument, ParseError> {
+    let mut decoder = DecoderState::new(stream, options);
+    decoder
+        .parse()
+        .map_err(|error| decoder.stream.locate(error))
+}
 
-    let root_id = decoder.root_id.unwrap();
-    let root = decoder.resolve_references(Value::Reference(root_id));
-    let end = decoder.next_value_record();
-    assert_eq!(end, Value::Bottom);
+/// Parse an NRBF message without resolving references, returning the value of its root and the
+/// values of all records by object id. Objects nested in another one remain `Value::Reference`s
+/// into the map, so that shared objects are kept once instead of being copied for every
+/// reference to them. The root is also kept in the map.
+pub fn parse_nrbf_unresolved<R: io::Read>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> Result<(Value, Map<i32, Value>), ParseError> {
+    let mut decoder = DecoderState::new(stream, options);
+    let (root, objects) = decoder
+        .parse_unresolved()
+        .map_err(|error| decoder.stream.locate(error))?;
+    let root = match root {
+        Value::Reference(id) => objects.get(&id).cloned().unwrap_or(Value::Null),
+        root => root,
+    };
+    Ok((root, objects))
+}
+
+/// Parse an NRBF message as far as possible. On an error, decoding stops as with
+/// `UnknownPolicy::SkipRemaining`: the root is returned with the values decoded so far, and the
+/// error along with it. Members and elements which were not read yet are `Null`, as are
+/// references to objects which were not decoded. Objects whose record contains the error are
+/// lost as a whole. Errors which leave no root at all return `Null`.
+pub fn parse_nrbf_lenient<R: io::Read>(
+    stream: &mut R,
+    options: ParseOptions,
+) -> (Value, Option<ParseError>) {
+    let mut decoder = DecoderState::new(stream, options);
+    decoder.lenient = true;
+    match decoder.parse() {
+        Ok(document) => (document.root, decoder.lenient_error.take()),
+        Err(error) => (Value::Null, Some(decoder.stream.locate(error))),
+    }
+}
 
-    root
+/// Decode a single primitive value of type `primitive_type`, as it is stored in a class member.
+/// The result is the same `Value` as for a member of that type.
+pub fn read_primitive<R: io::Read>(
+    primitive_type: PrimitiveType,
+    stream: &mut R,
+) -> Result<Value, ParseError> {
+    let mut stream = CountingReader::new(stream);
+    primitive_type
+        .read(&mut stream)
+        .map_err(|error| stream.locate(error))
 }
@@ -0,0 +1,102 @@
+use crate::prelude::*;
+use crate::{Class, RecordType};
+
+/// A decoded record, as reported to `ParseOptions::on_record`.
+#[derive(Debug, Clone)]
+pub struct RecordTrace {
+    pub record_type: RecordType,
+    /// The object id defined or referenced by the record, if any.
+    pub object_id: Option<i32>,
+    /// The offset of the start of the record in the stream.
+    pub offset: u64,
+}
+
+pub type OnRecord = Box<dyn FnMut(&RecordTrace)>;
+
+/// What to do when encountering a record which cannot be decoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownPolicy {
+    /// Fail with `UnexpectedEnumValue`.
+    #[default]
+    Fail,
+    /// Stop reading and return what has been decoded so far. Records are variable-length, so the
+    /// unknown record cannot be skipped on its own. Members and elements which were not read yet
+    /// are `Null`, and the position of the stream afterwards is unspecified.
+    SkipRemaining,
+}
+
+pub struct ParseOptions {
+    /// Invoked for each decoded record, e.g. to log the record stream of a failing file.
+    pub on_record: Option<OnRecord>,
+    pub on_unknown_record: UnknownPolicy,
+    /// Replace a `MemberReference` to an object which has already been decoded by a copy of its
+    /// value. References to objects further ahead in the stream are kept as `Value::Reference`.
+    /// This mostly matters for `RecordReader`, which does not resolve references otherwise.
+    pub eager_resolve_known_refs: bool,
+    /// The maximum number of elements of an array of objects or strings, and of nulls in a single
+    /// `ObjectNullMultiple` record, `None` for no limit. Such a record can stand for any number of
+    /// elements, each of which is decoded into a `Value`. Primitive arrays are exempt, as their
+    /// elements are stored inline and so are bounded by the input.
+    pub max_array_length: Option<usize>,
+    /// The maximum nesting depth of records, and of references while resolving them, `None` for
+    /// no limit. Both are decoded recursively, so deeper input would overflow the stack.
+    pub max_depth: Option<usize>,
+    /// The maximum number of values a message may decode to, `None` for no limit. This counts
+    /// the elements of arrays of objects or strings as they are decoded, as runs of nulls can
+    /// stand for any number of them.
+    pub max_values: Option<usize>,
+    /// The maximum size in bytes of the copies made while resolving references, `None` for no
+    /// limit. An object referenced from several places is copied into each of them but the
+    /// first, so small input can otherwise stand for an exponential amount of memory. The size
+    /// is approximate, it counts the `Value`s of a copy along with their strings and buffers.
+    pub max_copied_bytes: Option<usize>,
+    /// Classes which are treated as if they had been defined before the message, by their
+    /// object id. Records such as `ClassWithId` can then refer to them, e.g. when decoding a
+    /// capture which starts in the middle of a session.
+    pub known_classes: Vec<(i32, Class)>,
+    /// Reject input which violates the format but can still be decoded, such as a class
+    /// referring to a library which has not been declared. Otherwise such a class has no
+    /// library name.
+    pub strict: bool,
+    /// Reject a `SerializationHeader` of a version other than 1.0. Otherwise the message is
+    /// decoded as version 1.0, which some writers emit under other minor versions.
+    pub strict_version: bool,
+    /// Decode the first null of an `ObjectNullMultiple` record as `Value::NullRun`, instead of
+    /// `Value::Null`, so that the writer can encode the run the same way.
+    pub preserve_null_runs: bool,
+    /// Wrap the value of every resolved reference below the root in `Value::Referenced`, along
+    /// with the id of the referenced object, to tell shared objects apart from equal copies.
+    /// Accessors and conversions such as `Value::members` do not look through the wrapper.
+    pub keep_reference_ids: bool,
+    /// Resolve the root from the `headerId` of the `SerializationHeader` if its `rootId` is 0,
+    /// for messages whose entry point is the header, such as some remoting payloads. The header
+    /// id is then reported as the root id.
+    pub root_from_header_id: bool,
+    /// Share equal class, library and member names between all classes of a message, rather than
+    /// only between the instances of each class. This saves memory when many classes have members
+    /// of the same name, at the cost of a lookup per name.
+    pub intern_strings: bool,
+}
+
+impl Default for ParseOptions {
+    /// The limits are loose enough for any message a .NET application is likely to write, but
+    /// keep malicious input from exhausting memory, time or the stack.
+    fn default() -> Self {
+        ParseOptions {
+            on_record: None,
+            on_unknown_record: UnknownPolicy::default(),
+            eager_resolve_known_refs: false,
+            max_array_length: Some(1 << 20),
+            max_depth: Some(128),
+            max_values: Some(1 << 22),
+            max_copied_bytes: Some(1 << 28),
+            known_classes: Vec::new(),
+            strict: false,
+            strict_version: false,
+            preserve_null_runs: false,
+            keep_reference_ids: false,
+            root_from_header_id: false,
+            intern_strings: false,
+        }
+    }
+}
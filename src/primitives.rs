@@ -1,93 +1,162 @@
-use std::io;
+use crate::error::{ParseError, ParseErrorKind};
+use crate::io;
+use crate::prelude::*;
+use crate::reader::CountingReader;
 
-fn read_or_panic<R: io::Read>(stream: &mut R, bytes: &mut [u8]) {
-    match stream.read_exact(bytes) {
-        Ok(()) => (),
-        Err(error) => panic!("Cannot read from stream: {error}"),
-    };
+fn read_bytes<R: io::Read>(stream: &mut R, bytes: &mut [u8]) -> Result<(), ParseError> {
+    stream.read_exact(bytes)?;
+    Ok(())
 }
 
-pub fn read_u8<R: io::Read>(stream: &mut R) -> u8 {
+pub fn read_u8<R: io::Read>(stream: &mut R) -> Result<u8, ParseError> {
     let mut bytes = [0u8; 1];
-    read_or_panic(stream, &mut bytes);
-    bytes[0]
+    read_bytes(stream, &mut bytes)?;
+    Ok(bytes[0])
+}
+
+/// Return the next byte without consuming it, the following read starts with the same byte.
+pub fn peek_u8<R: io::Read>(stream: &mut CountingReader<'_, R>) -> Result<u8, ParseError> {
+    Ok(stream.peek()?)
 }
 
-pub fn read_i8<R: io::Read>(stream: &mut R) -> i8 {
+pub fn read_i8<R: io::Read>(stream: &mut R) -> Result<i8, ParseError> {
     let mut bytes = [0u8; 1];
-    read_or_panic(stream, &mut bytes);
-    i8::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(i8::from_le_bytes(bytes))
 }
 
-pub fn read_u16<R: io::Read>(stream: &mut R) -> u16 {
+pub fn read_u16<R: io::Read>(stream: &mut R) -> Result<u16, ParseError> {
     let mut bytes = [0u8; 2];
-    read_or_panic(stream, &mut bytes);
-    u16::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
 }
 
-pub fn read_i16<R: io::Read>(stream: &mut R) -> i16 {
+pub fn read_i16<R: io::Read>(stream: &mut R) -> Result<i16, ParseError> {
     let mut bytes = [0u8; 2];
-    read_or_panic(stream, &mut bytes);
-    i16::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(i16::from_le_bytes(bytes))
 }
 
-pub fn read_u32<R: io::Read>(stream: &mut R) -> u32 {
+pub fn read_u32<R: io::Read>(stream: &mut R) -> Result<u32, ParseError> {
     let mut bytes = [0u8; 4];
-    read_or_panic(stream, &mut bytes);
-    u32::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
 }
 
-pub fn read_i32<R: io::Read>(stream: &mut R) -> i32 {
+pub fn read_i32<R: io::Read>(stream: &mut R) -> Result<i32, ParseError> {
     let mut bytes = [0u8; 4];
-    read_or_panic(stream, &mut bytes);
-    i32::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
 }
 
-pub fn read_u64<R: io::Read>(stream: &mut R) -> u64 {
+pub fn read_u64<R: io::Read>(stream: &mut R) -> Result<u64, ParseError> {
     let mut bytes = [0u8; 8];
-    read_or_panic(stream, &mut bytes);
-    u64::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
 }
 
-pub fn read_i64<R: io::Read>(stream: &mut R) -> i64 {
+pub fn read_i64<R: io::Read>(stream: &mut R) -> Result<i64, ParseError> {
     let mut bytes = [0u8; 8];
-    read_or_panic(stream, &mut bytes);
-    i64::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(i64::from_le_bytes(bytes))
 }
 
-pub fn read_f32<R: io::Read>(stream: &mut R) -> f32 {
+pub fn read_f32<R: io::Read>(stream: &mut R) -> Result<f32, ParseError> {
     let mut bytes = [0u8; 4];
-    read_or_panic(stream, &mut bytes);
-    f32::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
 }
 
-pub fn read_f64<R: io::Read>(stream: &mut R) -> f64 {
+pub fn read_f64<R: io::Read>(stream: &mut R) -> Result<f64, ParseError> {
     let mut bytes = [0u8; 8];
-    read_or_panic(stream, &mut bytes);
-    f64::from_le_bytes(bytes)
+    read_bytes(stream, &mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
 }
 
+/// Read the length of a `LengthPrefixedString`: seven bits per byte, least significant first,
+/// with the high bit set on all but the last byte. Like the other primitive readers, errors are
+/// not located, their `offset` is 0.
+///
 /// For reference see:
 /// https://winprotocoldoc.blob.core.windows.net/productionwindowsarchives/MS-NRBF/%5bMS-NRBF%5d.pdf#%5B%7B%22num%22%3A66%2C%22gen%22%3A0%7D%2C%7B%22name%22%3A%22XYZ%22%7D%2C69%2C670%2C0%5D
-pub fn read_variable_length<R: io::Read>(stream: &mut R) -> usize {
-    let mut length = 0usize;
-    let mut num_bytes = 0;
-    loop {
-        let byte = read_u8(stream);
-        length += ((byte & 0b01111111) as usize) << (num_bytes * 7);
-        num_bytes += 1;
+pub fn read_variable_length<R: io::Read>(stream: &mut R) -> Result<usize, ParseError> {
+    // The length is at most `i32::MAX`, encoded in up to five bytes.
+    let mut length = 0u64;
+    for num_bytes in 0..5 {
+        let byte = read_u8(stream)?;
+        length |= ((byte & 0b01111111) as u64) << (num_bytes * 7);
         if (byte & 0b10000000) == 0 {
-            return length;
+            return i32::try_from(length)
+                .map(|length| length as usize)
+                .map_err(|_| ParseErrorKind::InvalidStringLength.into());
         }
     }
+    Err(ParseErrorKind::InvalidStringLength.into())
+}
+
+/// Read `length` bytes. The buffer grows with the data actually read, so a corrupt length fails
+/// with `UnexpectedEof` instead of allocating up front.
+pub fn read_byte_array<R: io::Read>(stream: &mut R, length: usize) -> Result<Vec<u8>, ParseError> {
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while bytes.len() < length {
+        let chunk = &mut chunk[..(length - bytes.len()).min(4096)];
+        read_bytes(stream, chunk)?;
+        bytes.extend_from_slice(chunk);
+    }
+    Ok(bytes)
 }
 
-pub fn read_lps<R: io::Read>(stream: &mut R) -> String {
-    let length = read_variable_length(stream);
-    let mut data = vec![0u8; length];
-    read_or_panic(stream, data.as_mut_slice());
-    match String::from_utf8(data) {
-        Ok(string) => string,
-        Err(err) => panic!("Failed to decode UTF8 data: {err}"),
+/// Read a `Char`, which is a single UTF-8 encoded character of one to four bytes.
+pub fn read_char<R: io::Read>(stream: &mut R) -> Result<char, ParseError> {
+    let mut bytes = [0u8; 4];
+    bytes[0] = read_u8(stream)?;
+    // Invalid leading bytes are left to the UTF-8 validation below.
+    let length = match bytes[0].leading_ones() {
+        2..=4 => bytes[0].leading_ones() as usize,
+        _ => 1,
+    };
+    read_bytes(stream, &mut bytes[1..length])?;
+    let string =
+        String::from_utf8(bytes[..length].to_vec()).map_err(ParseErrorKind::InvalidUtf8)?;
+    Ok(string.chars().next().unwrap())
+}
+
+/// Read a `LengthPrefixedString`, a UTF-8 string prefixed by its length in bytes as read by
+/// `read_variable_length`.
+pub fn read_lps<R: io::Read>(stream: &mut R) -> Result<String, ParseError> {
+    let length = read_variable_length(stream)?;
+    let data = read_byte_array(stream, length)?;
+    String::from_utf8(data).map_err(|error| ParseErrorKind::InvalidUtf8(error).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_are_little_endian() {
+        assert_eq!(read_i16(&mut [0xfe, 0xff].as_slice()).unwrap(), -2);
+        assert_eq!(
+            read_i32(&mut [0x01, 0x00, 0x00, 0x00].as_slice()).unwrap(),
+            1
+        );
+        let bytes = [0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01];
+        assert_eq!(read_i64(&mut bytes.as_slice()).unwrap(), 0x0102030405060708);
+        assert_eq!(read_u16(&mut [0x34, 0x12].as_slice()).unwrap(), 0x1234);
+        let bytes = [0x78, 0x56, 0x34, 0x12];
+        assert_eq!(read_u32(&mut bytes.as_slice()).unwrap(), 0x12345678);
+        let bytes = [0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x81];
+        assert_eq!(read_u64(&mut bytes.as_slice()).unwrap(), 0x8102030405060708);
+    }
+
+    #[test]
+    fn floats_are_little_endian() {
+        assert_eq!(
+            read_f32(&mut [0x00, 0x00, 0x80, 0x3f].as_slice()).unwrap(),
+            1.0
+        );
+        let bytes = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0xbf];
+        assert_eq!(read_f64(&mut bytes.as_slice()).unwrap(), -1.0);
     }
 }
@@ -0,0 +1,91 @@
+use alloc::collections::VecDeque;
+
+use crate::error::ParseError;
+use crate::io;
+use crate::prelude::*;
+
+/// The number of bytes kept before, and read after, the position of an error.
+const CONTEXT_SIZE: usize = 16;
+
+/// Wraps a reader, keeping track of the number of bytes consumed and the most recent bytes.
+pub struct CountingReader<'a, R: io::Read> {
+    inner: &'a mut R,
+    offset: u64,
+    recent: VecDeque<u8>,
+    /// A byte which has been read from `inner` but not yet consumed.
+    peeked: Option<u8>,
+}
+
+impl<'a, R: io::Read> CountingReader<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        CountingReader {
+            inner,
+            offset: 0,
+            recent: VecDeque::with_capacity(CONTEXT_SIZE),
+            peeked: None,
+        }
+    }
+
+    /// Look at the next byte without consuming it.
+    pub fn peek(&mut self) -> io::Result<u8> {
+        if let Some(byte) = self.peeked {
+            return Ok(byte);
+        }
+        let mut byte = [0u8; 1];
+        self.inner.read_exact(&mut byte)?;
+        self.peeked = Some(byte[0]);
+        Ok(byte[0])
+    }
+
+    /// The number of bytes consumed so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Attach the current offset and the surrounding bytes to `error`. This reads up to
+    /// `CONTEXT_SIZE` bytes past the current offset, the stream must not be used afterwards.
+    pub fn locate(&mut self, error: ParseError) -> ParseError {
+        let offset = self.offset();
+        let mut context = self.recent.iter().copied().collect::<Vec<_>>();
+        let context_start = offset - context.len() as u64;
+
+        let mut after = [0u8; CONTEXT_SIZE];
+        let mut num_after = 0;
+        if let Some(byte) = self.peeked.take() {
+            after[0] = byte;
+            num_after = 1;
+        }
+        while num_after < CONTEXT_SIZE {
+            match self.inner.read(&mut after[num_after..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => num_after += n,
+            }
+        }
+        context.extend_from_slice(&after[..num_after]);
+
+        error.at(offset, context_start, context)
+    }
+}
+
+impl<R: io::Read> io::Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match self.peeked.take() {
+            Some(byte) if !buf.is_empty() => {
+                buf[0] = byte;
+                1
+            }
+            peeked => {
+                self.peeked = peeked;
+                self.inner.read(buf)?
+            }
+        };
+        self.offset += n as u64;
+
+        let tail = &buf[n.saturating_sub(CONTEXT_SIZE)..n];
+        let excess = (self.recent.len() + tail.len()).saturating_sub(CONTEXT_SIZE);
+        self.recent.drain(..excess);
+        self.recent.extend(tail);
+
+        Ok(n)
+    }
+}
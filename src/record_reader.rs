@@ -0,0 +1,100 @@
+use alloc::collections::VecDeque;
+
+use crate::io;
+use crate::prelude::*;
+use crate::value::Value;
+use crate::{is_method_message, DecoderState, ParseError, ParseOptions, RecordType};
+
+/// Reads the objects of an NRBF message one at a time, without resolving references.
+///
+/// Unlike `parse_nrbf`, decoded values are handed out instead of being kept until the end of the
+/// message, so large messages can be processed with bounded memory. Each item is an object id and
+/// its value, in which nested objects appear as `Value::Reference`. The objects decoded from one
+/// top-level record, including nested ones, are yielded ordered by id. Iteration ends after the `MessageEnd`
+/// record, or after the first error. Method messages, which have no object id, are yielded with
+/// the root id of the header, before their call array. Values they store in the call array
+/// remain in the array instead of being moved into the message.
+///
+/// With `ParseOptions::eager_resolve_known_refs`, yielded values are kept as well, so that later
+/// references to them can be resolved. This trades the bounded memory for fewer references.
+pub struct RecordReader<'a, R: io::Read> {
+    decoder: DecoderState<'a, R>,
+    pending: VecDeque<(i32, Value)>,
+    failed: bool,
+}
+
+impl<'a, R: io::Read> RecordReader<'a, R> {
+    pub fn new(stream: &'a mut R, options: ParseOptions) -> Self {
+        let mut decoder = DecoderState::new(stream, options);
+        decoder.defined = Some(Vec::new());
+        RecordReader {
+            decoder,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    /// The root id of the message, once the `SerializationHeader` has been read.
+    pub fn root_id(&self) -> Option<i32> {
+        self.decoder.root_id
+    }
+
+    /// The type of the record that defined the root object, once it has been read.
+    pub fn root_record_type(&self) -> Option<RecordType> {
+        self.decoder.root_record_type
+    }
+
+    /// The number of records read so far, including nested ones.
+    pub fn records_read(&self) -> u64 {
+        self.decoder.records_read
+    }
+
+    /// The number of bytes consumed from the stream so far.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.decoder.stream.offset()
+    }
+
+    fn read_next(&mut self) -> Result<(), ParseError> {
+        if self.decoder.root_id.is_none() {
+            self.decoder.read_header()?;
+        }
+        while self.pending.is_empty() && !self.decoder.message_end {
+            let value = self.decoder.next_top_level_record()?;
+            match self.decoder.root_id {
+                Some(root_id) if is_method_message(&value) => {
+                    self.pending.push_back((root_id, value))
+                }
+                _ => {}
+            }
+            let defined = self.decoder.defined.as_mut();
+            let mut ids = defined.map(core::mem::take).unwrap_or_default();
+            ids.sort_unstable();
+            let keep = self.decoder.options.eager_resolve_known_refs;
+            let values = &mut self.decoder.values;
+            self.pending.extend(ids.into_iter().filter_map(|id| {
+                let value = if keep {
+                    values.get(&id).cloned()
+                } else {
+                    values.remove(&id)
+                };
+                value.map(|value| (id, value))
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl<R: io::Read> Iterator for RecordReader<'_, R> {
+    type Item = Result<(i32, Value), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if let Err(error) = self.read_next() {
+            self.failed = true;
+            return Some(Err(self.decoder.stream.locate(error)));
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
@@ -1,11 +1,44 @@
-use std::collections::HashMap;
-use std::fmt::Display;
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::sync::Arc;
+use core::fmt::Display;
+use core::ops::ControlFlow;
 
+use crate::prelude::*;
+
+/// The map of object members. Without the `std` feature, members are kept in a `BTreeMap`.
+#[cfg(feature = "std")]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// A class, library or member name. The names of a class are shared by its instances, and with
+/// `ParseOptions::intern_strings` by all classes using them.
+pub type Name = Arc<str>;
+
+/// `DateTime.MaxValue.Ticks` in .NET.
+const MAX_TICKS: i64 = 3_155_378_975_999_999_999;
+
+/// How the ticks of a `DateTime` are to be interpreted.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DateTimeKind {
+    Unspecified,
+    Utc,
+    Local,
+}
+
+/// A decoded value.
+///
+/// The derived `PartialEq` compares floats numerically, so a value containing a NaN is not equal
+/// to itself. Use `eq_bitwise` to compare floats by their bit patterns instead.
 #[derive(PartialEq, Debug, Clone)]
 pub enum Value {
     Null,
-    Byte(u8),
+    /// The first of `n` nulls which were encoded as one `ObjectNullMultiple` record, kept with
+    /// `ParseOptions::preserve_null_runs`. The other `n - 1` nulls follow as `Null`.
+    NullRun(usize),
     Bool(bool),
+    /// An unsigned byte, `PrimitiveType::Byte`. Signed bytes, `PrimitiveType::SByte`, are `I8`.
     U8(u8),
     U32(u32),
     U64(u64),
@@ -15,16 +48,692 @@ pub enum Value {
     F32(f32),
     F64(f64),
     String(String),
-    Array(Vec<usize>, Vec<usize>, Vec<Value>),
-    Object(String, HashMap<String, Value>),
+    Char(char),
+    /// A .NET `DateTime`: the number of 100ns ticks since 0001-01-01T00:00:00, and its kind.
+    DateTime(i64, DateTimeKind),
+    /// A .NET `TimeSpan`: a signed duration in 100ns ticks.
+    TimeSpan(i64),
+    /// A .NET `Decimal`, in its culture-invariant string form such as `-12.50`.
+    Decimal(String),
+    /// An array of the given lengths and lower bounds, one per dimension. The elements are
+    /// stored flat in row-major order.
+    Array(Vec<usize>, Vec<i32>, Vec<Value>),
+    /// A single-dimensional array of bytes, stored contiguously. Arrays of `SByte` remain
+    /// `Array`s of `I8`: binary data is stored as `byte[]` in .NET, `sbyte[]` rarely holds more
+    /// than a few values.
+    Bytes(Vec<u8>),
+    /// An instance of a class: the class name, the name of the library defining the class
+    /// (`None` for system classes) and the members by name.
+    Object(Name, Option<Name>, Map<Name, Value>),
     Reference(i32),
+    /// The value of a resolved reference along with the id of the object it referred to, kept
+    /// with `ParseOptions::keep_reference_ids`. The same id in several places means that the
+    /// object is shared rather than copied.
+    Referenced(i32, Box<Value>),
+    /// A remoting call of `method_name` on `type_name`. The call context is the id of the
+    /// `LogicalCallContext` as a string if sent inline, or the `LogicalCallContext` object if sent
+    /// in the call array.
+    MethodCall {
+        method_name: String,
+        type_name: String,
+        call_context: Option<Box<Value>>,
+        args: Option<Vec<Value>>,
+    },
+    MethodReturn {
+        return_value: Option<Box<Value>>,
+        call_context: Option<Box<Value>>,
+        args: Option<Vec<Value>>,
+    },
     Bottom,
 }
 
-fn fmt_indent(v: &Value, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
+impl Value {
+    /// The name of the variant, for diagnostics.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "Null",
+            Value::NullRun(_) => "NullRun",
+            Value::Bool(_) => "Bool",
+            Value::U8(_) => "U8",
+            Value::U32(_) => "U32",
+            Value::U64(_) => "U64",
+            Value::I8(_) => "I8",
+            Value::I32(_) => "I32",
+            Value::I64(_) => "I64",
+            Value::F32(_) => "F32",
+            Value::F64(_) => "F64",
+            Value::String(_) => "String",
+            Value::Char(_) => "Char",
+            Value::DateTime(..) => "DateTime",
+            Value::TimeSpan(_) => "TimeSpan",
+            Value::Decimal(_) => "Decimal",
+            Value::Array(..) => "Array",
+            Value::Bytes(_) => "Bytes",
+            Value::Object(..) => "Object",
+            Value::Reference(_) => "Reference",
+            Value::Referenced(..) => "Referenced",
+            Value::MethodCall { .. } => "MethodCall",
+            Value::MethodReturn { .. } => "MethodReturn",
+            Value::Bottom => "Bottom",
+        }
+    }
+
+    /// Like `type_name`, but including the class name of objects, e.g. `Object(Player)`.
+    fn describe(&self) -> String {
+        match self {
+            Value::Object(class_name, ..) => format!("Object({class_name})"),
+            other => other.type_name().to_owned(),
+        }
+    }
+
+    /// Start building an instance of class `class_name`, see `ObjectBuilder`.
+    pub fn object(class_name: impl Into<Name>) -> ObjectBuilder {
+        ObjectBuilder {
+            class_name: class_name.into(),
+            library_name: None,
+            members: Map::new(),
+        }
+    }
+
+    /// A single-dimensional array of `values`.
+    pub fn array(values: Vec<Value>) -> Value {
+        Value::Array(vec![values.len()], vec![0], values)
+    }
+
+    /// Display the value with at most `max_depth` levels of nested arrays and objects and at
+    /// most `max_elements` elements or members each, eliding the rest as `…`. A depth of 0 elides
+    /// the contents of the value itself. Useful for logging values of unknown size.
+    pub fn display_limited(&self, max_depth: usize, max_elements: usize) -> LimitedDisplay<'_> {
+        LimitedDisplay {
+            value: self,
+            max_depth,
+            max_elements,
+        }
+    }
+
+    /// The single-line representation, as printed by `{:#}`.
+    pub fn to_compact_string(&self) -> String {
+        format!("{self:#}")
+    }
+
+    /// Whether this is `Null`, or the `NullRun` starting a run of nulls.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null | Value::NullRun(_))
+    }
+
+    /// Whether this is an unresolved reference to another object.
+    pub fn is_reference(&self) -> bool {
+        matches!(self, Value::Reference(_))
+    }
+
+    /// `None` for `Null`, otherwise the value itself. Useful for nullable members.
+    pub fn as_option(&self) -> Option<&Value> {
+        (!self.is_null()).then_some(self)
+    }
+
+    /// The lengths of the dimensions of an array, including `Bytes`.
+    pub fn lengths(&self) -> Option<Cow<'_, [usize]>> {
+        match self {
+            Value::Array(lengths, _, _) => Some(Cow::Borrowed(lengths)),
+            Value::Bytes(bytes) => Some(Cow::Owned(vec![bytes.len()])),
+            _ => None,
+        }
+    }
+
+    /// The lower bounds of the dimensions of an array, including `Bytes`. These are nonzero only
+    /// for offset arrays.
+    pub fn lower_bounds(&self) -> Option<&[i32]> {
+        match self {
+            Value::Array(_, lower_bounds, _) => Some(lower_bounds),
+            Value::Bytes(_) => Some(&[0]),
+            _ => None,
+        }
+    }
+
+    /// The flat, row-major elements of an array. `None` for `Bytes`, whose elements are not
+    /// stored as values, see `iter_array` instead.
+    pub fn elements(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(_, _, values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The number of elements of an array, including `Bytes`, or of members of an object.
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Value::Array(_, _, values) => Some(values.len()),
+            Value::Bytes(bytes) => Some(bytes.len()),
+            Value::Object(_, _, members) => Some(members.len()),
+            _ => None,
+        }
+    }
+
+    /// Whether an array or object has no elements or members, see `len`.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Iterate over the flat, row-major elements of an array. The elements of `Bytes` are
+    /// `U8`s.
+    pub fn iter_array(&self) -> Option<ArrayIter<'_>> {
+        match self {
+            Value::Array(_, _, values) => Some(ArrayIter {
+                values: values.iter(),
+                ..Default::default()
+            }),
+            Value::Bytes(bytes) => Some(ArrayIter {
+                bytes: bytes.iter(),
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// The class name of an object.
+    pub fn class_name(&self) -> Option<&str> {
+        match self {
+            Value::Object(class_name, ..) => Some(class_name),
+            _ => None,
+        }
+    }
+
+    /// The name of the library defining the class of an object, `None` for system classes.
+    pub fn library_name(&self) -> Option<&str> {
+        match self {
+            Value::Object(_, library_name, _) => library_name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The members of an object.
+    pub fn members(&self) -> Option<&Map<Name, Value>> {
+        match self {
+            Value::Object(_, _, members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Borrow the text of a `String`, without cloning it like `TryFrom` does.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Move the buffer out of `Bytes` without copying it, unlike `TryFrom` which converts byte by
+    /// byte. Returns the value itself if it is not `Bytes`.
+    pub fn into_bytes(self) -> Result<Vec<u8>, Value> {
+        match self {
+            Value::Bytes(v) => Ok(v),
+            other => Err(other),
+        }
+    }
+
+    /// Widen any integer variant to `i64`. Returns `None` for non-integers and values which do
+    /// not fit.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::U8(v) => Some(*v as i64),
+            Value::U32(v) => Some(*v as i64),
+            Value::U64(v) => i64::try_from(*v).ok(),
+            Value::I8(v) => Some(*v as i64),
+            Value::I32(v) => Some(*v as i64),
+            Value::I64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Widen any integer variant to `u64`. Returns `None` for non-integers and negative values.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::U8(v) => Some(*v as u64),
+            Value::U32(v) => Some(*v as u64),
+            Value::U64(v) => Some(*v),
+            Value::I8(v) => u64::try_from(*v).ok(),
+            Value::I32(v) => u64::try_from(*v).ok(),
+            Value::I64(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Widen any integer or float variant to `f64`. Returns `None` for non-numbers and integers
+    /// which cannot be represented exactly.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::F32(v) => Some(*v as f64),
+            Value::F64(v) => Some(*v),
+            Value::U64(v) => {
+                let float = *v as f64;
+                (float < u64::MAX as f64 && float as u64 == *v).then_some(float)
+            }
+            other => {
+                let v = other.as_i64()?;
+                let float = v as f64;
+                (float < i64::MAX as f64 && float as i64 == v).then_some(float)
+            }
+        }
+    }
+
+    /// Reinterpret a `U64` or `I64` holding .NET ticks, as some serializers store a `DateTime`,
+    /// as an `Unspecified` `DateTime`. Returns `None` for other variants and for ticks outside
+    /// the range of `DateTime`.
+    pub fn reinterpret_ticks_as_datetime(&self) -> Option<Value> {
+        let ticks = match self {
+            Value::U64(v) => i64::try_from(*v).ok()?,
+            Value::I64(v) => *v,
+            _ => return None,
+        };
+        (0..=MAX_TICKS)
+            .contains(&ticks)
+            .then_some(Value::DateTime(ticks, DateTimeKind::Unspecified))
+    }
+
+    /// Convert a `DateTime` to a UTC timestamp. `Local` and `Unspecified` times carry no offset,
+    /// their ticks are treated as if they were UTC. Returns `None` for other variants and for
+    /// ticks past the end of year 9999.
+    #[cfg(feature = "chrono")]
+    pub fn as_datetime_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        const TICKS_PER_SECOND: i64 = 10_000_000;
+
+        let Value::DateTime(ticks, _) = self else {
+            return None;
+        };
+        if !(0..=MAX_TICKS).contains(ticks) {
+            return None;
+        }
+        let epoch = chrono::NaiveDate::from_ymd_opt(1, 1, 1)?.and_hms_opt(0, 0, 0)?;
+        let seconds = chrono::TimeDelta::try_seconds(ticks / TICKS_PER_SECOND)?;
+        let nanoseconds = chrono::TimeDelta::nanoseconds(ticks % TICKS_PER_SECOND * 100);
+        let time = epoch
+            .checked_add_signed(seconds)?
+            .checked_add_signed(nanoseconds)?;
+        Some(time.and_utc())
+    }
+
+    /// Parse a `Decimal`. Returns `None` for other variants and for strings which are not in the
+    /// invariant format, an optional `-` followed by digits with an optional `.` separator.
+    #[cfg(feature = "rust_decimal")]
+    pub fn as_decimal(&self) -> Option<rust_decimal::Decimal> {
+        let Value::Decimal(v) = self else {
+            return None;
+        };
+        let digits = v.strip_prefix('-').unwrap_or(v);
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+        let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if !is_digits(integer) || !is_digits(fraction) {
+            return None;
+        }
+        rust_decimal::Decimal::from_str_exact(v).ok()
+    }
+
+    /// Like `==`, but follows references instead of comparing their ids, so that graphs which
+    /// differ only in how their objects are numbered are equal. References in `self` are looked
+    /// up in `objects`, those in `other` in `other_objects`, e.g. the objects of a
+    /// `ParsedDocument`. A reference to an id which is not in its map only equals another such
+    /// reference.
+    pub fn structurally_eq(
+        &self,
+        objects: &Map<i32, Value>,
+        other: &Value,
+        other_objects: &Map<i32, Value>,
+    ) -> bool {
+        StructuralEq {
+            objects: [objects, other_objects],
+            comparing: Vec::new(),
+        }
+        .eq(self, other)
+    }
+
+    /// Like `==`, but compares floats by their bit patterns, so that NaN equals NaN when the bits
+    /// match.
+    pub fn eq_bitwise(&self, other: &Value) -> bool {
+        fn all_eq_bitwise(a: &[Value], b: &[Value]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.eq_bitwise(b))
+        }
+        fn option_eq_bitwise(a: &Option<Box<Value>>, b: &Option<Box<Value>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => a.eq_bitwise(b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+        }
+        fn args_eq_bitwise(a: &Option<Vec<Value>>, b: &Option<Vec<Value>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => all_eq_bitwise(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+        }
+
+        match (self, other) {
+            (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+            (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+            (Value::Array(a_lengths, a_bounds, a), Value::Array(b_lengths, b_bounds, b)) => {
+                a_lengths == b_lengths && a_bounds == b_bounds && all_eq_bitwise(a, b)
+            }
+            (Value::Object(a_class, a_library, a), Value::Object(b_class, b_library, b)) => {
+                a_class == b_class
+                    && a_library == b_library
+                    && a.len() == b.len()
+                    && a.iter()
+                        .all(|(name, a)| b.get(name).is_some_and(|b| a.eq_bitwise(b)))
+            }
+            (
+                Value::MethodCall {
+                    method_name: a_method_name,
+                    type_name: a_type_name,
+                    call_context: a_call_context,
+                    args: a_args,
+                },
+                Value::MethodCall {
+                    method_name: b_method_name,
+                    type_name: b_type_name,
+                    call_context: b_call_context,
+                    args: b_args,
+                },
+            ) => {
+                a_method_name == b_method_name
+                    && a_type_name == b_type_name
+                    && option_eq_bitwise(a_call_context, b_call_context)
+                    && args_eq_bitwise(a_args, b_args)
+            }
+            (
+                Value::MethodReturn {
+                    return_value: a_return_value,
+                    call_context: a_call_context,
+                    args: a_args,
+                },
+                Value::MethodReturn {
+                    return_value: b_return_value,
+                    call_context: b_call_context,
+                    args: b_args,
+                },
+            ) => {
+                option_eq_bitwise(a_return_value, b_return_value)
+                    && option_eq_bitwise(a_call_context, b_call_context)
+                    && args_eq_bitwise(a_args, b_args)
+            }
+            (Value::Referenced(a_id, a), Value::Referenced(b_id, b)) => {
+                a_id == b_id && a.eq_bitwise(b)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Get an element of a (multidimensional) array, including `Bytes`, by its indices. Elements
+    /// are stored in row-major order, the indices are offset by the lower bounds of their
+    /// dimension, so they may be negative. Returns `None` if the value is not an array, or the
+    /// indices are out of bounds.
+    pub fn get_md(&self, indices: &[i32]) -> Option<&Value> {
+        let (lengths, lower_bounds) = (self.lengths()?, self.lower_bounds()?);
+        if indices.len() != lengths.len() {
+            return None;
+        }
+
+        let mut offset = 0;
+        for ((index, length), lower_bound) in indices.iter().zip(&*lengths).zip(lower_bounds) {
+            let index = usize::try_from(i64::from(*index) - i64::from(*lower_bound)).ok()?;
+            if index >= *length {
+                return None;
+            }
+            offset = offset * length + index;
+        }
+        self.iter_array()?.nth(offset)
+    }
+
+    /// Collect the leaves of the tree along with their paths, e.g. `Inventory[0].Name`. Members
+    /// are appended as `.name`, ordered by name, and array elements as `[i]`, indexing the flat
+    /// elements. Everything but arrays, objects and method messages is a leaf, including `Null`
+    /// and `Reference`s, which are not followed. Of method messages, only the return value and
+    /// arguments are collected. A leaf at the root has an empty path.
+    pub fn flatten(&self) -> Vec<(String, &Value)> {
+        let mut leaves = Vec::new();
+        flatten_into(self, &mut String::new(), &mut leaves);
+        leaves
+    }
+
+    /// Call `f` for every node of the graph depth-first, in pre-order, until it returns
+    /// `ControlFlow::Break`. Members are visited ordered by name, bytes as `U8` values. A
+    /// `Reference` is visited itself and then followed into `objects`, unless its id has been
+    /// followed before, so every object is visited once even in cyclic graphs. Pass an empty map
+    /// to treat references as leaves.
+    pub fn walk_dfs<'a, F: FnMut(&'a Value) -> ControlFlow<()>>(
+        &'a self,
+        objects: &'a Map<i32, Value>,
+        f: F,
+    ) -> ControlFlow<()> {
+        walk(self, objects, false, f)
+    }
+
+    /// Like `walk_dfs`, but visits the graph breadth-first, level by level.
+    pub fn walk_bfs<'a, F: FnMut(&'a Value) -> ControlFlow<()>>(
+        &'a self,
+        objects: &'a Map<i32, Value>,
+        f: F,
+    ) -> ControlFlow<()> {
+        walk(self, objects, true, f)
+    }
+}
+
+/// Class and member names are printed verbatim, unless they contain characters which would
+/// break up the layout. Those are quoted and escaped like strings.
+struct DisplayName<'a>(&'a str);
+
+impl Display for DisplayName<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let is_special = |c: char| c.is_control() || matches!(c, '"' | '{' | '}');
+        if self.0.is_empty() || self.0.contains(is_special) {
+            write!(f, "{:?}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// The state of `Value::structurally_eq`.
+struct StructuralEq<'a> {
+    objects: [&'a Map<i32, Value>; 2],
+    /// The pairs of values being compared after following a reference. Comparing a pair again
+    /// means that the graphs are cyclic, and the pair is assumed to be equal.
+    comparing: Vec<(*const Value, *const Value)>,
+}
+
+impl<'a> StructuralEq<'a> {
+    fn eq(&mut self, a: &'a Value, b: &'a Value) -> bool {
+        // Ids are irrelevant, only the values they were resolved to are compared.
+        if let Value::Referenced(_, a) = a {
+            return self.eq(a, b);
+        }
+        if let Value::Referenced(_, b) = b {
+            return self.eq(a, b);
+        }
+        if !a.is_reference() && !b.is_reference() {
+            return self.eq_resolved(a, b);
+        }
+
+        let resolve = |value: &'a Value, objects: &'a Map<i32, Value>| match value {
+            Value::Reference(id) => objects.get(id).ok_or(*id),
+            value => Ok(value),
+        };
+        match (resolve(a, self.objects[0]), resolve(b, self.objects[1])) {
+            (Ok(a), Ok(b)) => {
+                let pair = (a as *const Value, b as *const Value);
+                if self.comparing.contains(&pair) {
+                    return true;
+                }
+                self.comparing.push(pair);
+                let eq = self.eq(a, b);
+                self.comparing.pop();
+                eq
+            }
+            (Err(_), Err(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn eq_resolved(&mut self, a: &'a Value, b: &'a Value) -> bool {
+        let mut all_eq = |a: &'a [Value], b: &'a [Value]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.eq(a, b))
+        };
+        match (a, b) {
+            (Value::Array(a_lengths, a_bounds, a), Value::Array(b_lengths, b_bounds, b)) => {
+                a_lengths == b_lengths && a_bounds == b_bounds && all_eq(a, b)
+            }
+            (Value::Object(a_class, a_library, a), Value::Object(b_class, b_library, b)) => {
+                a_class == b_class
+                    && a_library == b_library
+                    && a.len() == b.len()
+                    && a.iter()
+                        .all(|(name, a)| b.get(name).is_some_and(|b| self.eq(a, b)))
+            }
+            (
+                Value::MethodCall {
+                    method_name: a_method_name,
+                    type_name: a_type_name,
+                    call_context: a_call_context,
+                    args: a_args,
+                },
+                Value::MethodCall {
+                    method_name: b_method_name,
+                    type_name: b_type_name,
+                    call_context: b_call_context,
+                    args: b_args,
+                },
+            ) => {
+                a_method_name == b_method_name
+                    && a_type_name == b_type_name
+                    && self.option_eq(a_call_context, b_call_context)
+                    && self.args_eq(a_args, b_args)
+            }
+            (
+                Value::MethodReturn {
+                    return_value: a_return_value,
+                    call_context: a_call_context,
+                    args: a_args,
+                },
+                Value::MethodReturn {
+                    return_value: b_return_value,
+                    call_context: b_call_context,
+                    args: b_args,
+                },
+            ) => {
+                self.option_eq(a_return_value, b_return_value)
+                    && self.option_eq(a_call_context, b_call_context)
+                    && self.args_eq(a_args, b_args)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn option_eq(&mut self, a: &'a Option<Box<Value>>, b: &'a Option<Box<Value>>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    fn args_eq(&mut self, a: &'a Option<Vec<Value>>, b: &'a Option<Vec<Value>>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.eq(a, b)),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+}
+
+fn flatten_into<'a>(value: &'a Value, path: &mut String, leaves: &mut Vec<(String, &'a Value)>) {
+    fn child<'a>(
+        value: &'a Value,
+        path: &mut String,
+        segment: core::fmt::Arguments,
+        leaves: &mut Vec<(String, &'a Value)>,
+    ) {
+        let length = path.len();
+        core::fmt::Write::write_fmt(path, segment).unwrap();
+        flatten_into(value, path, leaves);
+        path.truncate(length);
+    }
+
+    let dot = if path.is_empty() { "" } else { "." };
+    match value {
+        Value::Array(_, _, values) => {
+            for (index, value) in values.iter().enumerate() {
+                child(value, path, format_args!("[{index}]"), leaves);
+            }
+        }
+        Value::Bytes(bytes) => {
+            for (index, byte) in bytes.iter().enumerate() {
+                let value = &BYTE_VALUES[*byte as usize];
+                child(value, path, format_args!("[{index}]"), leaves);
+            }
+        }
+        Value::Object(_, _, members) => {
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_unstable_by_key(|(name, _)| *name);
+            for (name, value) in members {
+                child(value, path, format_args!("{dot}{name}"), leaves);
+            }
+        }
+        Value::MethodCall { args, .. } => {
+            for (index, value) in args.iter().flatten().enumerate() {
+                child(value, path, format_args!("{dot}args[{index}]"), leaves);
+            }
+        }
+        Value::MethodReturn {
+            return_value, args, ..
+        } => {
+            if let Some(return_value) = return_value {
+                child(
+                    return_value,
+                    path,
+                    format_args!("{dot}return_value"),
+                    leaves,
+                );
+            }
+            for (index, value) in args.iter().flatten().enumerate() {
+                child(value, path, format_args!("{dot}args[{index}]"), leaves);
+            }
+        }
+        Value::Referenced(_, value) => flatten_into(value, path, leaves),
+        leaf => leaves.push((path.clone(), leaf)),
+    }
+}
+
+/// How a value is printed: pretty-printed at `indent`, or on a single line if `indent` is `None`.
+/// Arrays and objects nested deeper than `max_depth`, and elements after the first
+/// `max_elements`, are elided as `…`.
+#[derive(Clone, Copy)]
+struct Layout {
+    indent: Option<usize>,
+    depth: usize,
+    max_depth: usize,
+    max_elements: usize,
+}
+
+impl Layout {
+    fn new(f: &core::fmt::Formatter<'_>, max_depth: usize, max_elements: usize) -> Self {
+        Layout {
+            indent: if f.alternate() { None } else { Some(0) },
+            depth: 0,
+            max_depth,
+            max_elements,
+        }
+    }
+
+    /// The layout of the elements of an array or object.
+    fn nested(self) -> Self {
+        Layout {
+            indent: self.indent.map(|indent| indent + 2),
+            depth: self.depth + 1,
+            ..self
+        }
+    }
+}
+
+/// Print `v` according to `layout`.
+fn fmt_indent(v: &Value, f: &mut core::fmt::Formatter<'_>, layout: Layout) -> core::fmt::Result {
     match v {
         Value::Null => write!(f, "Null"),
-        Value::Byte(v) => write!(f, "{v}u8"),
+        Value::NullRun(n) => write!(f, "NullRun({n})"),
         Value::Bool(v) => write!(f, "{v}"),
         Value::U8(v) => write!(f, "{v}u8"),
         Value::U32(v) => write!(f, "{v}u32"),
@@ -32,43 +741,668 @@ fn fmt_indent(v: &Value, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std:
         Value::I8(v) => write!(f, "{v}i8"),
         Value::I32(v) => write!(f, "{v}i32"),
         Value::I64(v) => write!(f, "{v}i64"),
-        Value::F32(v) => write!(f, "{v}uf32"),
+        Value::F32(v) => write!(f, "{v}f32"),
         Value::F64(v) => write!(f, "{v}f64"),
-        Value::String(v) => write!(f, "{v}"),
-        Value::Array(_, _, vs) => {
-            writeln!(f, "[").unwrap();
-            for v in vs {
-                write!(f, "{:>1$}", "", indent + 2).unwrap();
-                fmt_indent(v, f, indent + 2).unwrap();
-                writeln!(f, ",").unwrap();
+        Value::String(v) => write!(f, "{v:?}"),
+        Value::Char(v) => write!(f, "{v:?}"),
+        Value::DateTime(ticks, kind) => write!(f, "DateTime({ticks}, {kind:?})"),
+        Value::TimeSpan(ticks) => write!(f, "TimeSpan({ticks})"),
+        Value::Decimal(v) => write!(f, "{v}m"),
+        Value::Array(lengths, lower_bounds, vs) => {
+            if lower_bounds.iter().any(|lower_bound| *lower_bound != 0) {
+                write!(f, "from {lower_bounds:?} ")?;
             }
-            write!(f, "{:>1$}]", "", indent)
+            fmt_dimensions(f, layout, lengths, vs)
+        }
+        // Bytes are printed on a single line, even when pretty-printing.
+        Value::Bytes(bytes) if layout.depth >= layout.max_depth && !bytes.is_empty() => {
+            write!(f, "[…]")
         }
-        Value::Object(class_name, members) => {
-            writeln!(f, "{class_name} {{").unwrap();
-            for (member, v) in members {
-                write!(f, "{:>1$}{member}: ", "", indent + 2).unwrap();
-                fmt_indent(v, f, indent + 2).unwrap();
-                writeln!(f, ",").unwrap();
+        Value::Bytes(bytes) => {
+            write!(f, "[")?;
+            for (index, byte) in bytes.iter().enumerate() {
+                let separator = if index == 0 { "" } else { ", " };
+                if index == layout.max_elements {
+                    write!(f, "{separator}…")?;
+                    break;
+                }
+                write!(f, "{separator}{byte}u8")?;
             }
-            write!(f, "{:>1$}}}", "", indent)
+            write!(f, "]")
+        }
+        Value::Object(class_name, _, members) => {
+            write!(f, "{} ", DisplayName(class_name))?;
+            fmt_elements(
+                f,
+                layout,
+                ("{", "}"),
+                " ",
+                members,
+                |f, (member, v), layout| {
+                    write!(f, "{}: ", DisplayName(member))?;
+                    fmt_indent(v, f, layout)
+                },
+            )
         }
         Value::Reference(v) => write!(f, "#{v}"),
+        Value::Referenced(id, v) => {
+            write!(f, "#{id} ")?;
+            fmt_indent(v, f, layout)
+        }
+        Value::MethodCall {
+            method_name,
+            type_name,
+            call_context,
+            args,
+        } => {
+            let names = [method_name, type_name].map(|name| Value::String(name.clone()));
+            let fields = [
+                Some(("method_name", core::slice::from_ref(&names[0]), false)),
+                Some(("type_name", core::slice::from_ref(&names[1]), false)),
+                call_context
+                    .as_deref()
+                    .map(|v| ("call_context", core::slice::from_ref(v), false)),
+                args.as_deref().map(|vs| ("args", vs, true)),
+            ];
+            fmt_method(f, layout, "MethodCall", fields)
+        }
+        Value::MethodReturn {
+            return_value,
+            call_context,
+            args,
+        } => {
+            let fields = [
+                return_value
+                    .as_deref()
+                    .map(|v| ("return_value", core::slice::from_ref(v), false)),
+                call_context
+                    .as_deref()
+                    .map(|v| ("call_context", core::slice::from_ref(v), false)),
+                args.as_deref().map(|vs| ("args", vs, true)),
+            ];
+            fmt_method(f, layout, "MethodReturn", fields)
+        }
         Value::Bottom => write!(f, "ERROR"),
     }
 }
 
+/// Print the flat, row-major `values` of an array as nested rows of its dimensions `lengths`.
+/// Values which do not match the lengths are printed flat.
+fn fmt_dimensions(
+    f: &mut core::fmt::Formatter<'_>,
+    layout: Layout,
+    lengths: &[usize],
+    values: &[Value],
+) -> core::fmt::Result {
+    let size = lengths
+        .iter()
+        .try_fold(1usize, |size, &length| size.checked_mul(length));
+    match lengths {
+        [rows, inner @ ..] if !inner.is_empty() && size == Some(values.len()) => {
+            let row_size = inner.iter().product::<usize>();
+            let rows = (0..*rows).map(|row| &values[row * row_size..(row + 1) * row_size]);
+            fmt_elements(f, layout, ("[", "]"), "", rows, |f, row, layout| {
+                fmt_dimensions(f, layout, inner, row)
+            })
+        }
+        _ => fmt_elements(f, layout, ("[", "]"), "", values, |f, v, layout| {
+            fmt_indent(v, f, layout)
+        }),
+    }
+}
+
+/// Print a method message like an object of class `name`. Each present field is a single value,
+/// or an array if flagged so.
+fn fmt_method<'a>(
+    f: &mut core::fmt::Formatter<'_>,
+    layout: Layout,
+    name: &str,
+    fields: impl IntoIterator<Item = Option<(&'a str, &'a [Value], bool)>>,
+) -> core::fmt::Result {
+    write!(f, "{name} ")?;
+    let fields = fields.into_iter().flatten();
+    fmt_elements(f, layout, ("{", "}"), " ", fields, |f, field, layout| {
+        let (name, vs, is_array) = field;
+        write!(f, "{name}: ")?;
+        if is_array {
+            fmt_elements(f, layout, ("[", "]"), "", vs, |f, v, layout| {
+                fmt_indent(v, f, layout)
+            })
+        } else {
+            fmt_indent(&vs[0], f, layout)
+        }
+    })
+}
+
+/// Write the elements of an array or object between `open` and `close`, one per line when
+/// pretty-printing. On a single line, non-empty elements are padded with `padding` on the inside.
+fn fmt_elements<T>(
+    f: &mut core::fmt::Formatter<'_>,
+    layout: Layout,
+    (open, close): (&str, &str),
+    padding: &str,
+    elements: impl IntoIterator<Item = T>,
+    mut fmt_element: impl FnMut(&mut core::fmt::Formatter<'_>, T, Layout) -> core::fmt::Result,
+) -> core::fmt::Result {
+    let mut elements = elements.into_iter().peekable();
+    if layout.depth >= layout.max_depth && elements.peek().is_some() {
+        return write!(f, "{open}…{close}");
+    }
+    let nested = layout.nested();
+
+    let Some(indent) = layout.indent else {
+        write!(f, "{open}")?;
+        let mut is_empty = true;
+        for (index, element) in elements.enumerate() {
+            write!(f, "{}", if is_empty { padding } else { ", " })?;
+            is_empty = false;
+            if index == layout.max_elements {
+                write!(f, "…")?;
+                break;
+            }
+            fmt_element(f, element, nested)?;
+        }
+        let padding = if is_empty { "" } else { padding };
+        return write!(f, "{padding}{close}");
+    };
+
+    writeln!(f, "{open}")?;
+    for (index, element) in elements.enumerate() {
+        write!(f, "{:>1$}", "", indent + 2)?;
+        if index == layout.max_elements {
+            writeln!(f, "…")?;
+            break;
+        }
+        fmt_element(f, element, nested)?;
+        writeln!(f, ",")?;
+    }
+    write!(f, "{:>1$}{close}", "", indent)
+}
+
+/// Builds a `Value::Object` member by member, see `Value::object`. Mostly useful for expected
+/// values in tests and for messages to be written.
+#[derive(Debug, Clone)]
+pub struct ObjectBuilder {
+    class_name: Name,
+    library_name: Option<Name>,
+    members: Map<Name, Value>,
+}
+
+impl ObjectBuilder {
+    /// Set the name of the library defining the class. Without it, the class is a system class.
+    pub fn library(mut self, library_name: impl Into<Name>) -> Self {
+        self.library_name = Some(library_name.into());
+        self
+    }
+
+    /// Add the member `name`, replacing an earlier member of the same name.
+    pub fn field(mut self, name: impl Into<Name>, value: Value) -> Self {
+        self.members.insert(name.into(), value);
+        self
+    }
+
+    pub fn build(self) -> Value {
+        Value::Object(self.class_name, self.library_name, self.members)
+    }
+}
+
+impl From<ObjectBuilder> for Value {
+    fn from(builder: ObjectBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Visit `root` and its descendants with a queue in breadth-first order, or with a stack
+/// otherwise. Children are pushed in reverse onto the stack so that they pop in order.
+fn walk<'a>(
+    root: &'a Value,
+    objects: &'a Map<i32, Value>,
+    breadth_first: bool,
+    mut f: impl FnMut(&'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut pending = VecDeque::from([root]);
+    let mut followed = BTreeSet::new();
+    loop {
+        let next = if breadth_first {
+            pending.pop_front()
+        } else {
+            pending.pop_back()
+        };
+        let Some(value) = next else {
+            return ControlFlow::Continue(());
+        };
+        f(value)?;
+
+        let start = pending.len();
+        match value {
+            Value::Reference(id) if followed.insert(*id) => pending.extend(objects.get(id)),
+            Value::Referenced(_, value) => pending.push_back(value),
+            Value::Array(_, _, values) => pending.extend(values),
+            Value::Bytes(bytes) => {
+                pending.extend(bytes.iter().map(|byte| &BYTE_VALUES[*byte as usize]))
+            }
+            Value::Object(_, _, members) => {
+                let mut members = members.iter().collect::<Vec<_>>();
+                members.sort_unstable_by_key(|(name, _)| *name);
+                pending.extend(members.into_iter().map(|(_, value)| value));
+            }
+            Value::MethodCall {
+                call_context, args, ..
+            } => {
+                pending.extend(call_context.as_deref());
+                pending.extend(args.iter().flatten());
+            }
+            Value::MethodReturn {
+                return_value,
+                call_context,
+                args,
+            } => {
+                pending.extend(return_value.as_deref());
+                pending.extend(call_context.as_deref());
+                pending.extend(args.iter().flatten());
+            }
+            _ => {}
+        }
+        if !breadth_first {
+            pending.make_contiguous()[start..].reverse();
+        }
+    }
+}
+
+/// Iterates over the elements of an array, like `iter_array`. Values other than `Array` and
+/// `Bytes` have no elements.
+impl<'a> IntoIterator for &'a Value {
+    type Item = &'a Value;
+    type IntoIter = ArrayIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_array().unwrap_or_default()
+    }
+}
+
+/// An iterator over the elements of an `Array` or `Bytes`, see `Value::iter_array`.
+#[derive(Debug, Clone, Default)]
+pub struct ArrayIter<'a> {
+    // At most one of them is not empty.
+    values: core::slice::Iter<'a, Value>,
+    bytes: core::slice::Iter<'a, u8>,
+}
+
+impl<'a> Iterator for ArrayIter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<&'a Value> {
+        let byte = |byte: &u8| &BYTE_VALUES[*byte as usize];
+        self.values.next().or_else(|| self.bytes.next().map(byte))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a Value> {
+        if self.bytes.len() == 0 {
+            return self.values.nth(n);
+        }
+        self.bytes.nth(n).map(|byte| &BYTE_VALUES[*byte as usize])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for ArrayIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let byte = |byte: &u8| &BYTE_VALUES[*byte as usize];
+        self.values
+            .next_back()
+            .or_else(|| self.bytes.next_back().map(byte))
+    }
+}
+
+impl ExactSizeIterator for ArrayIter<'_> {
+    fn len(&self) -> usize {
+        self.values.len() + self.bytes.len()
+    }
+}
+
+/// `{}` pretty-prints values over multiple indented lines, `{:#}` prints them on a single line.
 impl Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fmt_indent(self, f, 0)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt_indent(self, f, Layout::new(f, usize::MAX, usize::MAX))
+    }
+}
+
+/// Displays a value like `Value`'s `Display`, but elides deeply nested and long arrays and
+/// objects as `…`. See `Value::display_limited`.
+#[derive(Debug, Clone, Copy)]
+pub struct LimitedDisplay<'a> {
+    value: &'a Value,
+    max_depth: usize,
+    max_elements: usize,
+}
+
+impl Display for LimitedDisplay<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt_indent(
+            self.value,
+            f,
+            Layout::new(f, self.max_depth, self.max_elements),
+        )
+    }
+}
+
+/// Objects are serialized as maps of their members, with the class name under `"$class"` and the
+/// library name, if any, under `"$library"`.
+/// References are serialized as `{"$ref": id}`, resolved references as their value.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        match self {
+            Value::Null | Value::NullRun(_) | Value::Bottom => serializer.serialize_none(),
+            Value::U8(v) => serializer.serialize_u8(*v),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::U32(v) => serializer.serialize_u32(*v),
+            Value::U64(v) => serializer.serialize_u64(*v),
+            Value::I8(v) => serializer.serialize_i8(*v),
+            Value::I32(v) => serializer.serialize_i32(*v),
+            Value::I64(v) => serializer.serialize_i64(*v),
+            Value::F32(v) => serializer.serialize_f32(*v),
+            Value::F64(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::DateTime(ticks, kind) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("ticks", ticks)?;
+                map.serialize_entry("kind", &format!("{kind:?}"))?;
+                map.end()
+            }
+            Value::TimeSpan(ticks) => serializer.serialize_i64(*ticks),
+            Value::Char(v) => serializer.serialize_char(*v),
+            Value::Decimal(v) => serializer.serialize_str(v),
+            Value::Array(_, _, values) => serializer.collect_seq(values),
+            Value::Bytes(bytes) => serializer.collect_seq(bytes),
+            Value::Object(class_name, library_name, members) => {
+                let len = members.len() + 1 + library_name.is_some() as usize;
+                let mut map = serializer.serialize_map(Some(len))?;
+                map.serialize_entry("$class", &**class_name)?;
+                if let Some(library_name) = library_name {
+                    map.serialize_entry("$library", &**library_name)?;
+                }
+                for (name, value) in members {
+                    map.serialize_entry(&**name, value)?;
+                }
+                map.end()
+            }
+            Value::Reference(id) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("$ref", id)?;
+                map.end()
+            }
+            Value::Referenced(_, value) => value.serialize(serializer),
+            Value::MethodCall {
+                method_name,
+                type_name,
+                call_context,
+                args,
+            } => {
+                let mut map = serializer.serialize_map(Some(5))?;
+                map.serialize_entry("$class", "MethodCall")?;
+                map.serialize_entry("method_name", method_name)?;
+                map.serialize_entry("type_name", type_name)?;
+                map.serialize_entry("call_context", call_context)?;
+                map.serialize_entry("args", args)?;
+                map.end()
+            }
+            Value::MethodReturn {
+                return_value,
+                call_context,
+                args,
+            } => {
+                let mut map = serializer.serialize_map(Some(4))?;
+                map.serialize_entry("$class", "MethodReturn")?;
+                map.serialize_entry("return_value", return_value)?;
+                map.serialize_entry("call_context", call_context)?;
+                map.serialize_entry("args", args)?;
+                map.end()
+            }
+        }
     }
 }
 
-fn expected_got<T>(expected: &str, got: &str) -> Result<T, String> {
-    Err(format!(
-        "Expected {expected}; Got {}",
-        &got[..100.min(got.len())]
-    ))
+/// The inverse of `Serialize`. Type information which is not part of the serialized form is
+/// guessed: integers become `I32` if they fit, then `I64` or `U64`, other numbers become `F64` and
+/// sequences become one-dimensional arrays. Maps are read as references, `DateTime`s, method
+/// messages or objects, depending on their keys. Maps with a `"$type"` instead of a `"$class"`
+/// are values tagged with their type, as written by `json::to_typed_json`, which restores the
+/// types guessing would get wrong.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct ValueVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "a serialized NRBF value")
+    }
+
+    fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(i32::try_from(v).map_or(Value::I64(v), Value::I32))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Value, E> {
+        match i64::try_from(v) {
+            Ok(v) => self.visit_i64(v),
+            Err(_) => Ok(Value::U64(v)),
+        }
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::F64(v))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        serde::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::array(values))
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut members = Map::<Name, Value>::new();
+        while let Some((name, value)) = map.next_entry::<String, Value>()? {
+            members.insert(name.into(), value);
+        }
+        from_members(members).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Interpret the entries of a serialized map, see the `Deserialize` implementation.
+#[cfg(feature = "serde")]
+fn from_members(mut members: Map<Name, Value>) -> Result<Value, String> {
+    // Take the optional entry `name`, which has to convert to `T`.
+    fn take<T: TryFrom<Value, Error = String>>(
+        members: &mut Map<Name, Value>,
+        name: &str,
+    ) -> Result<Option<T>, String> {
+        match members.remove(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => T::try_from(value).map(Some),
+        }
+    }
+    let has_only = |members: &Map<Name, Value>, names: &[&str]| {
+        members.len() == names.len() && names.iter().all(|name| members.contains_key(*name))
+    };
+
+    if !members.contains_key("$class") && members.contains_key("$type") {
+        return from_tagged(members);
+    }
+    if has_only(&members, &["$ref"]) {
+        let id = take::<i32>(&mut members, "$ref")?.ok_or("Expected a reference id")?;
+        return Ok(Value::Reference(id));
+    }
+    if has_only(&members, &["ticks", "kind"]) {
+        let ticks = members.remove("ticks").as_ref().and_then(Value::as_i64);
+        let ticks = ticks.ok_or("Expected DateTime ticks")?;
+        let kind = match take::<String>(&mut members, "kind")?.as_deref() {
+            Some("Unspecified") => DateTimeKind::Unspecified,
+            Some("Utc") => DateTimeKind::Utc,
+            Some("Local") => DateTimeKind::Local,
+            kind => return Err(format!("Unexpected DateTime kind {kind:?}")),
+        };
+        return Ok(Value::DateTime(ticks, kind));
+    }
+
+    let class_name = take::<String>(&mut members, "$class")?.ok_or("Expected a \"$class\"")?;
+    let args = |members: &mut Map<Name, Value>| match members.remove("args") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(_, _, values)) => Ok(Some(values)),
+        Some(other) => expected_got("method arguments", &other),
+    };
+    let boxed = |members: &mut Map<Name, Value>, name| {
+        members
+            .remove(name)
+            .filter(|value| !value.is_null())
+            .map(Box::new)
+    };
+    match class_name.as_str() {
+        "MethodCall"
+            if has_only(
+                &members,
+                &["method_name", "type_name", "call_context", "args"],
+            ) =>
+        {
+            Ok(Value::MethodCall {
+                method_name: take(&mut members, "method_name")?.ok_or("Expected a method name")?,
+                type_name: take(&mut members, "type_name")?.ok_or("Expected a type name")?,
+                call_context: boxed(&mut members, "call_context"),
+                args: args(&mut members)?,
+            })
+        }
+        "MethodReturn" if has_only(&members, &["return_value", "call_context", "args"]) => {
+            Ok(Value::MethodReturn {
+                return_value: boxed(&mut members, "return_value"),
+                call_context: boxed(&mut members, "call_context"),
+                args: args(&mut members)?,
+            })
+        }
+        _ => {
+            let library_name = take::<String>(&mut members, "$library")?;
+            Ok(Value::Object(
+                class_name.into(),
+                library_name.map(Name::from),
+                members,
+            ))
+        }
+    }
+}
+
+/// Interpret a value tagged with its type, see the `Deserialize` implementation.
+#[cfg(feature = "serde")]
+fn from_tagged(mut members: Map<Name, Value>) -> Result<Value, String> {
+    let type_name = match members.remove("$type") {
+        Some(Value::String(type_name)) => type_name,
+        other => return expected_got("a type name", &other.unwrap_or(Value::Null)),
+    };
+    let mut entry = |name: &str| {
+        members
+            .remove(name)
+            .ok_or_else(|| format!("Expected \"{name}\" of {type_name}"))
+    };
+    let elements = |value| match value {
+        Value::Array(_, _, values) => Ok(values),
+        other => expected_got("an array", &other),
+    };
+    fn integers<T: TryFrom<i64>>(values: &[Value]) -> Option<Vec<T>> {
+        let integer = |value: &Value| value.as_i64().and_then(|v| T::try_from(v).ok());
+        values.iter().map(integer).collect()
+    }
+
+    let value = if type_name == "Array" {
+        let lengths = integers(&elements(entry("lengths")?)?).ok_or("Expected lengths")?;
+        let lower_bounds =
+            integers(&elements(entry("lower_bounds")?)?).ok_or("Expected lower bounds")?;
+        Value::Array(lengths, lower_bounds, elements(entry("values")?)?)
+    } else {
+        let value = entry("value")?;
+        let invalid = || format!("Expected {type_name}; Got {}", value.describe());
+        let float = || match &value {
+            Value::String(v) if v == "NaN" => Some(f64::NAN),
+            Value::String(v) if v == "Infinity" => Some(f64::INFINITY),
+            Value::String(v) if v == "-Infinity" => Some(f64::NEG_INFINITY),
+            other => other.as_f64(),
+        };
+        let unsigned = || value.as_u64().ok_or_else(invalid);
+        let signed = || value.as_i64().ok_or_else(invalid);
+        let narrow = |_| invalid();
+        match type_name.as_str() {
+            "U8" => Value::U8(unsigned()?.try_into().map_err(narrow)?),
+            "U32" => Value::U32(unsigned()?.try_into().map_err(narrow)?),
+            "U64" => Value::U64(unsigned()?),
+            "I8" => Value::I8(signed()?.try_into().map_err(narrow)?),
+            "I64" => Value::I64(signed()?),
+            "TimeSpan" => Value::TimeSpan(signed()?),
+            "F32" => Value::F32(float().ok_or_else(invalid)? as f32),
+            "F64" => Value::F64(float().ok_or_else(invalid)?),
+            "Char" => {
+                let mut chars = value.as_str().ok_or_else(invalid)?.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(v), None) => Value::Char(v),
+                    _ => return Err(invalid()),
+                }
+            }
+            "Decimal" => Value::Decimal(value.as_str().ok_or_else(invalid)?.to_owned()),
+            "Bytes" => {
+                let byte = |value: &Value| value.as_u64().and_then(|v| u8::try_from(v).ok());
+                let bytes = match &value {
+                    Value::Array(_, _, values) => values.iter().map(byte).collect(),
+                    _ => None,
+                };
+                Value::Bytes(bytes.ok_or_else(invalid)?)
+            }
+            _ => return Err(format!("Unexpected type {type_name:?}")),
+        }
+    };
+    match members.keys().next() {
+        Some(name) => Err(format!("Unexpected entry {name:?} of {type_name}")),
+        None => Ok(value),
+    }
+}
+
+fn expected_got<T>(expected: &str, got: &Value) -> Result<T, String> {
+    Err(format!("Expected {expected}; Got {}", got.describe()))
 }
 
 impl TryFrom<&Value> for bool {
@@ -77,7 +1411,7 @@ impl TryFrom<&Value> for bool {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::Bool(v) => Ok(*v),
-            _ => expected_got("Bool", &value.to_string()),
+            _ => expected_got("Bool", value),
         }
     }
 }
@@ -88,7 +1422,7 @@ impl TryFrom<&Value> for u8 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::U8(v) => Ok(*v),
-            _ => expected_got("U8", &value.to_string()),
+            _ => expected_got("U8", value),
         }
     }
 }
@@ -99,7 +1433,7 @@ impl TryFrom<&Value> for u32 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::U32(v) => Ok(*v),
-            _ => expected_got("U32", &value.to_string()),
+            _ => expected_got("U32", value),
         }
     }
 }
@@ -110,7 +1444,7 @@ impl TryFrom<&Value> for u64 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::U64(v) => Ok(*v),
-            _ => expected_got("U64", &value.to_string()),
+            _ => expected_got("U64", value),
         }
     }
 }
@@ -121,7 +1455,7 @@ impl TryFrom<&Value> for i8 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::I8(v) => Ok(*v),
-            _ => expected_got("I8", &value.to_string()),
+            _ => expected_got("I8", value),
         }
     }
 }
@@ -132,7 +1466,7 @@ impl TryFrom<&Value> for i32 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::I32(v) => Ok(*v),
-            _ => expected_got("I32", &value.to_string()),
+            _ => expected_got("I32", value),
         }
     }
 }
@@ -143,7 +1477,7 @@ impl TryFrom<&Value> for i64 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::I64(v) => Ok(*v),
-            _ => expected_got("I64", &value.to_string()),
+            _ => expected_got("I64", value),
         }
     }
 }
@@ -154,7 +1488,7 @@ impl TryFrom<&Value> for f32 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::F32(v) => Ok(*v),
-            _ => expected_got("F32", &value.to_string()),
+            _ => expected_got("F32", value),
         }
     }
 }
@@ -165,7 +1499,18 @@ impl TryFrom<&Value> for f64 {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::F64(v) => Ok(*v),
-            _ => expected_got("F64", &value.to_string()),
+            _ => expected_got("F64", value),
+        }
+    }
+}
+
+impl TryFrom<&Value> for char {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Char(v) => Ok(*v),
+            _ => expected_got("Char", value),
         }
     }
 }
@@ -176,22 +1521,133 @@ impl TryFrom<&Value> for String {
     fn try_from(value: &Value) -> Result<Self, String> {
         match value {
             Value::String(v) => Ok(v.clone()),
-            _ => expected_got("String", &value.to_string()),
+            _ => expected_got("String", value),
         }
     }
 }
 
+/// Every possible `Value::U8`, so that borrowed conversions can hand out references to the elements
+/// of a `Value::Bytes`.
+static BYTE_VALUES: [Value; 256] = {
+    let mut values = [const { Value::Null }; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        // The replaced `Value::Null` owns nothing, forgetting it only satisfies const evaluation.
+        core::mem::forget(core::mem::replace(&mut values[byte], Value::U8(byte as u8)));
+        byte += 1;
+    }
+    values
+};
+
 impl<'a, T: TryFrom<&'a Value, Error = String>> TryFrom<&'a Value> for Vec<T> {
     type Error = String;
 
     fn try_from(value: &'a Value) -> Result<Self, String> {
         match value {
             Value::Array(_, _, v) => v.iter().map(T::try_from).collect::<Result<_, _>>(),
-            _ => expected_got("Array", &value.to_string()),
+            Value::Bytes(bytes) => bytes
+                .iter()
+                .map(|byte| T::try_from(&BYTE_VALUES[*byte as usize]))
+                .collect::<Result<_, _>>(),
+            _ => expected_got("Array", value),
+        }
+    }
+}
+
+/// Converts an array of exactly `N` elements, without allocating.
+impl<'a, T: TryFrom<&'a Value, Error = String>, const N: usize> TryFrom<&'a Value> for [T; N] {
+    type Error = String;
+
+    fn try_from(value: &'a Value) -> Result<Self, String> {
+        let (length, element): (usize, &dyn Fn(usize) -> &'a Value) = match value {
+            Value::Array(_, _, v) => (v.len(), &|index| &v[index]),
+            Value::Bytes(bytes) => (bytes.len(), &|index| &BYTE_VALUES[bytes[index] as usize]),
+            _ => return expected_got("Array", value),
+        };
+        if length != N {
+            return Err(format!(
+                "Expected Array of {N} elements; Got {length} elements"
+            ));
+        }
+
+        let mut error = None;
+        let elements: [Option<T>; N] =
+            core::array::from_fn(|index| match T::try_from(element(index)) {
+                Ok(element) => Some(element),
+                Err(e) => {
+                    error.get_or_insert(e);
+                    None
+                }
+            });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(elements.map(Option::unwrap)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Map<Name, Value> {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Object(_, _, members) => Ok(members.clone()),
+            _ => expected_got("Object", value),
         }
     }
 }
 
-// Null,
-// Byte(u8),
-// Object(String, HashMap<String, Value>),
+// Owned conversions of scalars just copy, there is nothing to move out.
+macro_rules! impl_try_from_owned {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = String;
+
+                fn try_from(value: Value) -> Result<Self, String> {
+                    <$t>::try_from(&value)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_owned!(bool, u8, u32, u64, i8, i32, i64, f32, f64, char);
+
+impl TryFrom<Value> for String {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(v) => Ok(v),
+            _ => expected_got("String", &value),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = String>> TryFrom<Value> for Vec<T> {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(_, _, v) => v.into_iter().map(T::try_from).collect::<Result<_, _>>(),
+            // Converts each byte, `Value::into_bytes` moves the buffer out as a whole instead.
+            Value::Bytes(bytes) => bytes
+                .into_iter()
+                .map(|byte| T::try_from(Value::U8(byte)))
+                .collect::<Result<_, _>>(),
+            _ => expected_got("Array", &value),
+        }
+    }
+}
+
+impl TryFrom<Value> for Map<Name, Value> {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Object(_, _, members) => Ok(members),
+            _ => expected_got("Object", &value),
+        }
+    }
+}
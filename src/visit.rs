@@ -0,0 +1,113 @@
+use crate::io;
+use crate::prelude::*;
+use crate::value::Value;
+use crate::{ParseError, ParseOptions, RecordReader};
+
+/// Observes the values of a message as they are decoded. All methods do nothing by default.
+///
+/// Each object is visited on its own, right after its record has been decoded, and dropped
+/// afterwards. Objects nested in another one are announced through `reference` and visited
+/// separately, see `RecordReader` for the order.
+pub trait Visitor {
+    /// Start visiting the object with id `id`.
+    fn record(&mut self, _id: i32) {}
+    fn begin_object(&mut self, _class_name: &str) {}
+    /// The value of member `name` is visited next. Members are visited ordered by name.
+    fn field(&mut self, _name: &str) {}
+    fn end_object(&mut self) {}
+    fn begin_array(&mut self, _lengths: &[usize]) {}
+    fn end_array(&mut self) {}
+    /// A primitive, string or `Null` value.
+    fn primitive(&mut self, _value: &Value) {}
+    /// A reference to the object with id `id`, which is visited on its own.
+    fn reference(&mut self, _id: i32) {}
+}
+
+/// Parse an NRBF message, reporting its values to `visitor` instead of building the whole
+/// object graph. References are not resolved.
+pub fn parse_nrbf_visit<R: io::Read, V: Visitor>(
+    stream: &mut R,
+    visitor: &mut V,
+) -> Result<(), ParseError> {
+    for object in RecordReader::new(stream, ParseOptions::default()) {
+        let (id, value) = object?;
+        visitor.record(id);
+        visit(&value, visitor);
+    }
+    Ok(())
+}
+
+fn visit<V: Visitor>(value: &Value, visitor: &mut V) {
+    match value {
+        Value::Array(lengths, _, values) => {
+            visitor.begin_array(lengths);
+            values.iter().for_each(|value| visit(value, visitor));
+            visitor.end_array();
+        }
+        Value::Bytes(bytes) => {
+            visitor.begin_array(&[bytes.len()]);
+            for byte in bytes {
+                visitor.primitive(&Value::U8(*byte));
+            }
+            visitor.end_array();
+        }
+        Value::Object(class_name, _, members) => {
+            visitor.begin_object(class_name);
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_unstable_by_key(|(name, _)| *name);
+            for (name, value) in members {
+                visitor.field(name);
+                visit(value, visitor);
+            }
+            visitor.end_object();
+        }
+        Value::MethodCall {
+            method_name,
+            type_name,
+            call_context,
+            args,
+        } => {
+            visitor.begin_object("MethodCall");
+            visitor.field("method_name");
+            visitor.primitive(&Value::String(method_name.clone()));
+            visitor.field("type_name");
+            visitor.primitive(&Value::String(type_name.clone()));
+            visit_method_fields(call_context, args, visitor);
+            visitor.end_object();
+        }
+        Value::MethodReturn {
+            return_value,
+            call_context,
+            args,
+        } => {
+            visitor.begin_object("MethodReturn");
+            if let Some(return_value) = return_value {
+                visitor.field("return_value");
+                visit(return_value, visitor);
+            }
+            visit_method_fields(call_context, args, visitor);
+            visitor.end_object();
+        }
+        Value::Reference(id) => visitor.reference(*id),
+        Value::Referenced(_, value) => visit(value, visitor),
+        primitive => visitor.primitive(primitive),
+    }
+}
+
+/// Visit the optional fields shared by `MethodCall` and `MethodReturn`.
+fn visit_method_fields<V: Visitor>(
+    call_context: &Option<Box<Value>>,
+    args: &Option<Vec<Value>>,
+    visitor: &mut V,
+) {
+    if let Some(call_context) = call_context {
+        visitor.field("call_context");
+        visit(call_context, visitor);
+    }
+    if let Some(args) = args {
+        visitor.field("args");
+        visitor.begin_array(&[args.len()]);
+        args.iter().for_each(|value| visit(value, visitor));
+        visitor.end_array();
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io;
+
+use crate::value::{DateTimeKind, Name, Value};
+use crate::{BinaryArrayType, BinaryType, PrimitiveType, RecordType};
+
+#[derive(Debug)]
+pub enum WriteError {
+    /// Writing to the underlying stream failed.
+    Io(io::Error),
+    /// The value cannot be represented by the records the writer supports.
+    Unsupported(String),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Io(error) => write!(f, "Cannot write to stream: {error}"),
+            WriteError::Unsupported(reason) => write!(f, "Cannot serialize value: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WriteError {
+    fn from(error: io::Error) -> Self {
+        WriteError::Io(error)
+    }
+}
+
+fn unsupported<T>(reason: &str) -> Result<T, WriteError> {
+    Err(WriteError::Unsupported(reason.to_owned()))
+}
+
+fn write_u8<W: io::Write>(stream: &mut W, value: u8) -> Result<(), WriteError> {
+    Ok(stream.write_all(&[value])?)
+}
+
+fn write_i32<W: io::Write>(stream: &mut W, value: i32) -> Result<(), WriteError> {
+    Ok(stream.write_all(&value.to_le_bytes())?)
+}
+
+fn write_length<W: io::Write>(stream: &mut W, value: usize) -> Result<(), WriteError> {
+    match i32::try_from(value) {
+        Ok(value) => write_i32(stream, value),
+        Err(_) => unsupported("Array length or bound exceeds i32"),
+    }
+}
+
+/// Inverse of `read_variable_length`.
+fn write_variable_length<W: io::Write>(
+    stream: &mut W,
+    mut length: usize,
+) -> Result<(), WriteError> {
+    loop {
+        let byte = (length & 0b01111111) as u8;
+        length >>= 7;
+        if length == 0 {
+            return write_u8(stream, byte);
+        }
+        write_u8(stream, byte | 0b10000000)?;
+    }
+}
+
+fn write_lps<W: io::Write>(stream: &mut W, value: &str) -> Result<(), WriteError> {
+    write_variable_length(stream, value.len())?;
+    Ok(stream.write_all(value.as_bytes())?)
+}
+
+/// The primitive type a value is written as, if it is a primitive.
+fn primitive_type(value: &Value) -> Option<PrimitiveType> {
+    Some(match value {
+        Value::Bool(_) => PrimitiveType::Boolean,
+        Value::U8(_) => PrimitiveType::Byte,
+        Value::U32(_) => PrimitiveType::UInt32,
+        Value::U64(_) => PrimitiveType::UInt64,
+        Value::I8(_) => PrimitiveType::SByte,
+        Value::I32(_) => PrimitiveType::Int32,
+        Value::I64(_) => PrimitiveType::Int64,
+        Value::F32(_) => PrimitiveType::Single,
+        Value::F64(_) => PrimitiveType::Double,
+        Value::Char(_) => PrimitiveType::Char,
+        Value::DateTime(..) => PrimitiveType::DateTime,
+        Value::TimeSpan(_) => PrimitiveType::TimeSpan,
+        Value::Decimal(_) => PrimitiveType::Decimal,
+        _ => return None,
+    })
+}
+
+/// Inverse of `PrimitiveType::read`, for the primitives returned by `primitive_type`.
+fn write_primitive<W: io::Write>(stream: &mut W, value: &Value) -> Result<(), WriteError> {
+    match value {
+        Value::Bool(v) => write_u8(stream, *v as u8),
+        Value::U8(v) => write_u8(stream, *v),
+        Value::U32(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::U64(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::I8(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::I32(v) => write_i32(stream, *v),
+        Value::I64(v) | Value::TimeSpan(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::F32(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::F64(v) => Ok(stream.write_all(&v.to_le_bytes())?),
+        Value::DateTime(ticks, kind) => {
+            let kind = match kind {
+                DateTimeKind::Unspecified => 0u64,
+                DateTimeKind::Utc => 1,
+                DateTimeKind::Local => 2,
+            };
+            Ok(stream.write_all(&(*ticks as u64 | kind << 62).to_le_bytes())?)
+        }
+        Value::Char(v) => Ok(stream.write_all(v.encode_utf8(&mut [0; 4]).as_bytes())?),
+        Value::Decimal(v) => write_lps(stream, v),
+        _ => unsupported("Expected a primitive"),
+    }
+}
+
+/// The type information of a class member, as written in a `...WithMembersAndTypes` record.
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum MemberType {
+    Primitive(u8),
+    String,
+    Object,
+    PrimitiveArray(u8),
+}
+
+impl MemberType {
+    fn of(value: &Value) -> Self {
+        if let Some(primitive_type) = primitive_type(value) {
+            return MemberType::Primitive(primitive_type as u8);
+        }
+        match value {
+            Value::String(_) => MemberType::String,
+            Value::Bytes(_) => MemberType::PrimitiveArray(PrimitiveType::Byte as u8),
+            Value::Array(..) => match single_primitive_type(value) {
+                Some(primitive_type) => MemberType::PrimitiveArray(primitive_type as u8),
+                None => MemberType::Object,
+            },
+            _ => MemberType::Object,
+        }
+    }
+
+    fn binary_type(&self) -> BinaryType {
+        match self {
+            MemberType::Primitive(_) => BinaryType::Primitive,
+            MemberType::String => BinaryType::String,
+            MemberType::Object => BinaryType::Object,
+            MemberType::PrimitiveArray(_) => BinaryType::PrimitiveArray,
+        }
+    }
+}
+
+/// The element type of an array which can be written as `ArraySinglePrimitive`.
+fn single_primitive_type(value: &Value) -> Option<PrimitiveType> {
+    let Value::Array(lengths, lower_bounds, values) = value else {
+        return None;
+    };
+    if lengths.len() != 1 || lower_bounds.first().copied().unwrap_or(0) != 0 {
+        return None;
+    }
+    element_primitive_type(values)
+}
+
+/// The primitive type shared by all `values`, if any.
+fn element_primitive_type(values: &[Value]) -> Option<PrimitiveType> {
+    let element_type = primitive_type(values.first()?)?;
+    values
+        .iter()
+        .all(|value| primitive_type(value) == Some(element_type))
+        .then_some(element_type)
+}
+
+struct EncoderState<'a, W: io::Write> {
+    stream: &'a mut W,
+
+    next_id: i32,
+    // Object ids of previously written class records, by class name, library and member layout.
+    classes: HashMap<ClassKey, i32>,
+    // Ids of previously written libraries, by name.
+    libraries: HashMap<String, i32>,
+    // The number of nulls following a `NullRun` which are covered by its record.
+    null_count: usize,
+}
+
+type ClassKey = (Name, Option<Name>, Vec<(Name, MemberType)>);
+
+impl<'a, W: io::Write> EncoderState<'a, W> {
+    fn new(stream: &'a mut W) -> Self {
+        EncoderState {
+            stream,
+            next_id: 1,
+            classes: Default::default(),
+            libraries: Default::default(),
+            null_count: 0,
+        }
+    }
+
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn write_record_type(&mut self, record_type: RecordType) -> Result<(), WriteError> {
+        write_u8(self.stream, record_type as u8)
+    }
+
+    /// Write a value that is stored as a record of its own.
+    fn write_value_record(&mut self, value: &Value) -> Result<(), WriteError> {
+        if self.null_count > 0 {
+            if value != &Value::Null {
+                return unsupported("A NullRun must be followed by the nulls it covers");
+            }
+            self.null_count -= 1;
+            return Ok(());
+        }
+        match value {
+            Value::Null => self.write_record_type(RecordType::ObjectNull),
+            Value::NullRun(count @ 0..=255) => {
+                self.write_record_type(RecordType::ObjectNullMultiple256)?;
+                self.null_count = count.saturating_sub(1);
+                write_u8(self.stream, *count as u8)
+            }
+            Value::NullRun(count) => {
+                self.write_record_type(RecordType::ObjectNullMultiple)?;
+                self.null_count = count - 1;
+                write_length(self.stream, *count)
+            }
+            Value::String(string) => {
+                self.write_record_type(RecordType::BinaryObjectString)?;
+                let id = self.next_id();
+                write_i32(self.stream, id)?;
+                write_lps(self.stream, string)
+            }
+            Value::Array(..) => self.write_array(value),
+            Value::Bytes(bytes) => {
+                self.write_record_type(RecordType::ArraySinglePrimitive)?;
+                let id = self.next_id();
+                write_i32(self.stream, id)?;
+                write_length(self.stream, bytes.len())?;
+                write_u8(self.stream, PrimitiveType::Byte as u8)?;
+                self.stream.write_all(bytes)?;
+                Ok(())
+            }
+            Value::Object(class_name, library_name, members) => {
+                self.write_object(class_name, library_name.as_ref(), members)
+            }
+            Value::Reference(_) => unsupported("References must be resolved"),
+            // Objects are written once per occurrence, shared or not.
+            Value::Referenced(_, value) => self.write_value_record(value),
+            _ => unsupported("Only objects, arrays, strings and nulls can be written as records"),
+        }
+    }
+
+    /// Write a `BinaryLibrary` record for `name`, unless it has been written before.
+    fn library_id(&mut self, name: &str) -> Result<i32, WriteError> {
+        if let Some(&id) = self.libraries.get(name) {
+            return Ok(id);
+        }
+        let id = self.next_id();
+        self.write_record_type(RecordType::BinaryLibrary)?;
+        write_i32(self.stream, id)?;
+        write_lps(self.stream, name)?;
+        self.libraries.insert(name.to_owned(), id);
+        Ok(id)
+    }
+
+    fn write_object(
+        &mut self,
+        class_name: &Name,
+        library_name: Option<&Name>,
+        members: &HashMap<Name, Value>,
+    ) -> Result<(), WriteError> {
+        let mut members = members.iter().collect::<Vec<_>>();
+        members.sort_by_key(|(name, _)| *name);
+        let layout = members
+            .iter()
+            .map(|(name, value)| ((*name).clone(), MemberType::of(value)))
+            .collect::<Vec<_>>();
+
+        let key = (class_name.clone(), library_name.cloned(), layout);
+        // The object id is taken first, the root must receive the first id. The library has to
+        // precede the class record though.
+        let id = self.next_id();
+        let library_id = match library_name {
+            Some(name) if !self.classes.contains_key(&key) => Some(self.library_id(name)?),
+            _ => None,
+        };
+        if let Some(&class_id) = self.classes.get(&key) {
+            self.write_record_type(RecordType::ClassWithId)?;
+            write_i32(self.stream, id)?;
+            write_i32(self.stream, class_id)?;
+        } else {
+            let record_type = match library_id {
+                Some(_) => RecordType::ClassWithMembersAndTypes,
+                None => RecordType::SystemClassWithMembersAndTypes,
+            };
+            self.write_record_type(record_type)?;
+            write_i32(self.stream, id)?;
+            write_lps(self.stream, class_name)?;
+            write_length(self.stream, key.2.len())?;
+            for (name, _) in &key.2 {
+                write_lps(self.stream, name)?;
+            }
+            for (_, member_type) in &key.2 {
+                write_u8(self.stream, member_type.binary_type() as u8)?;
+            }
+            for (_, member_type) in &key.2 {
+                match member_type {
+                    MemberType::Primitive(primitive_type)
+                    | MemberType::PrimitiveArray(primitive_type) => {
+                        write_u8(self.stream, *primitive_type)?
+                    }
+                    MemberType::String | MemberType::Object => (),
+                }
+            }
+            if let Some(library_id) = library_id {
+                write_i32(self.stream, library_id)?;
+            }
+            self.classes.insert(key, id);
+        }
+
+        for (_, value) in members {
+            if primitive_type(value).is_some() {
+                write_primitive(self.stream, value)?;
+            } else {
+                self.write_value_record(value)?;
+            }
+        }
+        self.end_null_run()
+    }
+
+    /// A run of nulls must not extend past the array or object it starts in.
+    fn end_null_run(&mut self) -> Result<(), WriteError> {
+        match self.null_count {
+            0 => Ok(()),
+            _ => unsupported("A NullRun covers more nulls than follow it"),
+        }
+    }
+
+    fn write_array(&mut self, value: &Value) -> Result<(), WriteError> {
+        let Value::Array(lengths, lower_bounds, values) = value else {
+            return unsupported("Expected an array");
+        };
+        // Readers rely on the lengths to know how many elements follow.
+        let size = lengths
+            .iter()
+            .try_fold(1usize, |size, &length| size.checked_mul(length));
+        if lengths.is_empty() || size != Some(values.len()) {
+            return unsupported("The lengths of an array do not match its number of elements");
+        }
+        if !lower_bounds.is_empty() && lower_bounds.len() != lengths.len() {
+            return unsupported("An array needs a lower bound for each dimension, or none");
+        }
+
+        let id = self.next_id();
+        if let Some(primitive_type) = single_primitive_type(value) {
+            self.write_record_type(RecordType::ArraySinglePrimitive)?;
+            write_i32(self.stream, id)?;
+            write_length(self.stream, values.len())?;
+            write_u8(self.stream, primitive_type as u8)?;
+            for value in values {
+                write_primitive(self.stream, value)?;
+            }
+            return Ok(());
+        }
+
+        let is_offset = lower_bounds.iter().any(|lower_bound| *lower_bound != 0);
+        let array_type = match (lengths.len() == 1, is_offset) {
+            (true, false) => BinaryArrayType::Single,
+            (true, true) => BinaryArrayType::SingleOffset,
+            (false, false) => BinaryArrayType::Rectangular,
+            (false, true) => BinaryArrayType::RectangularOffset,
+        };
+
+        self.write_record_type(RecordType::BinaryArray)?;
+        write_i32(self.stream, id)?;
+        write_u8(self.stream, array_type as u8)?;
+        write_length(self.stream, lengths.len())?;
+        for length in lengths {
+            write_length(self.stream, *length)?;
+        }
+        if is_offset {
+            for lower_bound in lower_bounds {
+                write_i32(self.stream, *lower_bound)?;
+            }
+        }
+        if let Some(primitive_type) = element_primitive_type(values) {
+            write_u8(self.stream, BinaryType::Primitive as u8)?;
+            write_u8(self.stream, primitive_type as u8)?;
+            for value in values {
+                write_primitive(self.stream, value)?;
+            }
+        } else {
+            write_u8(self.stream, BinaryType::Object as u8)?;
+            for value in values {
+                self.write_value_record(value)?;
+            }
+        }
+        self.end_null_run()
+    }
+}
+
+/// Serialize `value` as an NRBF message. The root must be an object, array or string; the writer
+/// supports the same subset of records as the reader.
+pub fn write_nrbf<W: io::Write>(value: &Value, stream: &mut W) -> Result<(), WriteError> {
+    let mut encoder = EncoderState::new(stream);
+
+    // The root is written first and therefore receives the first id.
+    encoder.write_record_type(RecordType::SerializationHeader)?;
+    write_i32(encoder.stream, encoder.next_id)?;
+    write_i32(encoder.stream, -1)?;
+    write_i32(encoder.stream, 1)?;
+    write_i32(encoder.stream, 0)?;
+
+    match value {
+        Value::Object(..) | Value::Array(..) | Value::Bytes(_) | Value::String(_) => {
+            encoder.write_value_record(value)?
+        }
+        _ => return unsupported("The root must be an object, array or string"),
+    }
+
+    encoder.write_record_type(RecordType::MessageEnd)
+}
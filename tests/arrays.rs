@@ -0,0 +1,399 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::{DateTimeKind, Value};
+use nrbf_rs::writer::write_nrbf;
+use nrbf_rs::{parse_nrbf, ParseErrorKind};
+
+/// An `ArraySinglePrimitive` root with the given element type and raw element bytes.
+fn single_primitive_array(primitive_type: u8, length: i32, elements: &[u8]) -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .u8(15)
+        .i32(1)
+        .i32(length)
+        .u8(primitive_type)
+        .bytes(elements)
+        .message_end()
+        .build()
+}
+
+#[test]
+fn byte_array_is_read_as_bytes() {
+    let bytes = single_primitive_array(2, 3, &[1, 2, 255]);
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(value, Value::Bytes(vec![1, 2, 255]));
+    assert_eq!(Vec::<u8>::try_from(&value).unwrap(), vec![1, 2, 255]);
+    assert_eq!(Vec::<u8>::try_from(value.clone()).unwrap(), vec![1, 2, 255]);
+
+    // The buffer is moved out as it is.
+    let Value::Bytes(buffer) = &value else {
+        unreachable!()
+    };
+    let data = buffer.as_ptr();
+    let moved = value.into_bytes().unwrap();
+    assert_eq!(moved.as_ptr(), data);
+    assert_eq!(moved, [1, 2, 255]);
+    let string = Value::String("x".to_owned());
+    assert_eq!(string.clone().into_bytes(), Err(string));
+}
+
+#[test]
+fn sbyte_array_keeps_its_sign() {
+    let bytes = single_primitive_array(10, 2, &[1, 255]);
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(vec![2], vec![0], vec![Value::I8(1), Value::I8(-1)])
+    );
+}
+
+#[test]
+fn numeric_arrays_are_decoded_in_bulk() {
+    let elements = [1.5f64, -2.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<_>>();
+    let bytes = single_primitive_array(6, 2, &elements);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(vec![2], vec![0], vec![Value::F64(1.5), Value::F64(-2.0)])
+    );
+
+    let bytes = single_primitive_array(7, 2, &[0xff, 0xff, 0x02, 0x00]);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(vec![2], vec![0], vec![Value::I32(-1), Value::I32(2)])
+    );
+}
+
+#[test]
+fn decimal_date_time_and_time_span_arrays() {
+    let mut elements = StreamBuilder::new().lps("-12.50").lps("3").build();
+    let bytes = single_primitive_array(5, 2, &elements);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let decimals = vec![
+        Value::Decimal("-12.50".to_owned()),
+        Value::Decimal("3".to_owned()),
+    ];
+    assert_eq!(value, Value::array(decimals));
+
+    // A UTC DateTime of 5 ticks and an Unspecified one of 6 ticks.
+    elements = [5u64 | 1 << 62, 6]
+        .iter()
+        .flat_map(|bits| bits.to_le_bytes())
+        .collect();
+    let bytes = single_primitive_array(13, 2, &elements);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let date_times = vec![
+        Value::DateTime(5, DateTimeKind::Utc),
+        Value::DateTime(6, DateTimeKind::Unspecified),
+    ];
+    assert_eq!(value, Value::array(date_times));
+
+    elements = [-1i64, 36_000_000_000]
+        .iter()
+        .flat_map(|ticks| ticks.to_le_bytes())
+        .collect();
+    let bytes = single_primitive_array(12, 2, &elements);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let time_spans = vec![Value::TimeSpan(-1), Value::TimeSpan(36_000_000_000)];
+    assert_eq!(value, Value::array(time_spans));
+
+    let mut written = Vec::new();
+    write_nrbf(&value, &mut written).unwrap();
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn truncated_numeric_array_is_an_error() {
+    let bytes = single_primitive_array(6, 2, &1.5f64.to_le_bytes());
+
+    assert!(parse_nrbf(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn truncated_byte_array_is_an_error() {
+    let bytes = single_primitive_array(2, 4, &[1, 2]);
+
+    assert!(parse_nrbf(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn single_offset_array_is_indexed_from_its_lower_bound() {
+    // BinaryArray, SingleOffset, rank 1, length 3, lower bound 5, of primitive Double.
+    let mut builder = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(3)
+        .i32(1)
+        .i32(3)
+        .i32(5)
+        .u8(0)
+        .u8(6);
+    for element in [0.5f64, 1.5, 2.5] {
+        builder = builder.bytes(&element.to_le_bytes());
+    }
+    let bytes = builder.message_end().build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(value.lengths().as_deref(), Some([3].as_slice()));
+    assert_eq!(value.lower_bounds(), Some([5].as_slice()));
+    assert_eq!(value.get_md(&[5]), Some(&Value::F64(0.5)));
+    assert_eq!(value.get_md(&[7]), Some(&Value::F64(2.5)));
+    assert_eq!(value.get_md(&[4]), None);
+    assert_eq!(value.get_md(&[8]), None);
+}
+
+#[test]
+fn negative_lower_bound_is_kept() {
+    // BinaryArray, RectangularOffset, rank 2, lengths 1 and 2, lower bounds -3 and 0, of I32.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(5)
+        .i32(2)
+        .i32(1)
+        .i32(2)
+        .i32(-3)
+        .i32(0)
+        .u8(0)
+        .u8(8)
+        .i32(10)
+        .i32(11)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(value.lower_bounds(), Some([-3, 0].as_slice()));
+    assert_eq!(value.get_md(&[-3, 1]), Some(&Value::I32(11)));
+    assert_eq!(value.get_md(&[0, 1]), None);
+
+    let mut written = Vec::new();
+    write_nrbf(&value, &mut written).unwrap();
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn negative_binary_array_length_is_an_error() {
+    // BinaryArray, Rectangular, rank 2, lengths 2 and -1, of primitive I32.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(2)
+        .i32(2)
+        .i32(2)
+        .i32(-1)
+        .u8(0)
+        .u8(8)
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::InvalidArrayLength { length: -1 }
+    ));
+}
+
+#[test]
+fn bytes_are_an_array_of_u8() {
+    let bytes = Value::Bytes(vec![7, 8, 9]);
+    assert_eq!(bytes.len(), Some(3));
+    assert_eq!(bytes.lengths().as_deref(), Some([3].as_slice()));
+    assert_eq!(bytes.lower_bounds(), Some([0].as_slice()));
+    assert_eq!(bytes.get_md(&[2]), Some(&Value::U8(9)));
+    assert_eq!(bytes.get_md(&[3]), None);
+
+    let elements = bytes.iter_array().unwrap();
+    assert_eq!(elements.len(), 3);
+    assert_eq!(
+        elements.rev().collect::<Vec<_>>(),
+        [&Value::U8(9), &Value::U8(8), &Value::U8(7)]
+    );
+    assert_eq!((&bytes).into_iter().nth(1), Some(&Value::U8(8)));
+}
+
+#[test]
+fn array_elements_can_be_iterated() {
+    let value = Value::Array(vec![2], vec![0], vec![Value::I32(1), Value::I32(2)]);
+    let elements = value
+        .iter_array()
+        .unwrap()
+        .map(i32::try_from)
+        .collect::<Result<Vec<_>, _>>();
+    assert_eq!(elements, Ok(vec![1, 2]));
+    assert_eq!((&value).into_iter().count(), 2);
+
+    let string = Value::String("x".to_owned());
+    assert!(string.iter_array().is_none());
+    assert_eq!((&string).into_iter().count(), 0);
+
+    assert_eq!(value.len(), Some(2));
+    assert_eq!(value.is_empty(), Some(false));
+    assert_eq!(Value::Bytes(vec![]).is_empty(), Some(true));
+    let object = Value::object("Point").field("x", Value::I32(0)).build();
+    assert_eq!(object.len(), Some(1));
+    assert_eq!(string.len(), None);
+    assert_eq!(string.is_empty(), None);
+}
+
+#[test]
+fn fixed_size_arrays() {
+    let value = Value::array(vec![Value::F32(1.0), Value::F32(0.5), Value::F32(2.0)]);
+    assert_eq!(<[f32; 3]>::try_from(&value), Ok([1.0, 0.5, 2.0]));
+    assert_eq!(
+        <[f32; 2]>::try_from(&value),
+        Err("Expected Array of 2 elements; Got 3 elements".to_owned())
+    );
+    assert!(<[i32; 3]>::try_from(&value).is_err());
+
+    let bytes = Value::Bytes(vec![1, 255]);
+    assert_eq!(<[u8; 2]>::try_from(&bytes), Ok([1, 255]));
+}
+
+#[test]
+fn class_typed_elements_are_records() {
+    // BinaryArray, Single, of BinaryType::Class with its class and library id.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(4)
+        .lps("C")
+        .i32(2)
+        .primitive_class(3, 8, 2)
+        .i32(7)
+        // ClassWithId reusing class 3.
+        .u8(1)
+        .i32(4)
+        .i32(3)
+        .i32(8)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let c = |v: i32| {
+        Value::object("C")
+            .library("lib")
+            .field("v", Value::I32(v))
+            .build()
+    };
+    assert_eq!(value, Value::array(vec![c(7), c(8)]));
+}
+
+#[test]
+fn jagged_primitive_array_elements_are_records() {
+    // BinaryArray, Jagged, of BinaryType::PrimitiveArray of Int32.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(1)
+        .i32(1)
+        .i32(2)
+        .u8(7)
+        .u8(8)
+        .u8(15)
+        .i32(2)
+        .i32(1)
+        .u8(8)
+        .i32(1)
+        .u8(15)
+        .i32(3)
+        .i32(2)
+        .u8(8)
+        .i32(2)
+        .i32(3)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let ints = |vs: &[i32]| Value::array(vs.iter().copied().map(Value::I32).collect());
+    assert_eq!(value, Value::array(vec![ints(&[1]), ints(&[2, 3])]));
+}
+
+/// A `Holder` with a `string[]` member `names` and an `object[]` member `items`, followed by the
+/// `ArraySingleString` and `ArraySingleObject` records of their values.
+fn single_array_members() -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("Holder")
+        .i32(2)
+        .lps("names")
+        .lps("items")
+        .u8(6)
+        .u8(5)
+        .u8(9)
+        .i32(2)
+        .u8(9)
+        .i32(3)
+        // ArraySingleString of a string, two nulls and a reference to the string.
+        .u8(17)
+        .i32(2)
+        .i32(4)
+        .object_string(4, "a")
+        .u8(13)
+        .u8(2)
+        .u8(9)
+        .i32(4)
+        // ArraySingleObject of a boxed Int32 and a null.
+        .u8(16)
+        .i32(3)
+        .i32(2)
+        .u8(8)
+        .u8(8)
+        .i32(7)
+        .u8(10)
+        .message_end()
+        .build()
+}
+
+#[test]
+fn string_and_object_array_members() {
+    let bytes = single_array_members();
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let a = Value::String("a".to_owned());
+    assert_eq!(
+        value,
+        Value::object("Holder")
+            .field(
+                "names",
+                Value::array(vec![a.clone(), Value::Null, Value::Null, a])
+            )
+            .field("items", Value::array(vec![Value::I32(7), Value::Null]))
+            .build()
+    );
+}
+
+#[test]
+fn null_run_does_not_leak_out_of_a_single_object_array() {
+    // An ArraySingleObject of one element, holding a run of two nulls.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(1)
+        .u8(13)
+        .u8(2)
+        .message_end()
+        .build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::NullRunTooLong { excess: 1 }
+    ));
+}
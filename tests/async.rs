@@ -0,0 +1,115 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{parse_nrbf_async, parse_nrbf_async_with_options, ParseErrorKind, ParseOptions};
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio::io::{AsyncReadExt, BufReader};
+
+#[tokio::test]
+async fn parses_from_async_reader() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+
+    let mut stream = bytes.as_slice();
+    let value = parse_nrbf_async(&mut stream).await.unwrap();
+    assert_eq!(value, Value::String("root".to_owned()));
+}
+
+#[tokio::test]
+async fn truncated_async_stream_is_an_error() {
+    let bytes = StreamBuilder::new().header(1).u8(6).i32(1).build();
+
+    let mut stream = bytes.as_slice();
+    let error = parse_nrbf_async(&mut stream).await.unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+}
+
+#[tokio::test]
+async fn async_options_are_applied() {
+    // ArraySingleObject of three nulls.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(3)
+        .u8(13)
+        .u8(3)
+        .message_end()
+        .build();
+
+    let options = ParseOptions {
+        max_array_length: Some(2),
+        ..Default::default()
+    };
+    let mut stream = bytes.as_slice();
+    let error = parse_nrbf_async_with_options(&mut stream, options)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong {
+            length: 3,
+            limit: 2
+        }
+    ));
+}
+
+#[tokio::test]
+async fn async_stream_is_left_after_message_end() {
+    let message = StreamBuilder::new()
+        .header(1)
+        .u8(17)
+        .i32(1)
+        .i32(2)
+        .object_string(2, "first")
+        .object_string(3, "second")
+        .message_end()
+        .build();
+    let bytes = [message.as_slice(), b"trailer"].concat();
+
+    // Small buffers hand the message to the decoder in many chunks.
+    for capacity in [1, 3, 8, 1024] {
+        let mut stream = BufReader::with_capacity(capacity, bytes.as_slice());
+        let value = parse_nrbf_async(&mut stream).await.unwrap();
+        assert_eq!(
+            value,
+            Value::array(vec![
+                Value::String("first".to_owned()),
+                Value::String("second".to_owned())
+            ])
+        );
+
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "trailer", "capacity {capacity}");
+    }
+}
+
+#[tokio::test]
+async fn async_records_are_traced_on_the_calling_task() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+
+    // The callback is not `Send`.
+    let offsets = Rc::new(RefCell::new(Vec::new()));
+    let sink = offsets.clone();
+    let options = ParseOptions {
+        on_record: Some(Box::new(move |trace| sink.borrow_mut().push(trace.offset))),
+        ..Default::default()
+    };
+    let mut stream = BufReader::with_capacity(4, bytes.as_slice());
+    parse_nrbf_async_with_options(&mut stream, options)
+        .await
+        .unwrap();
+    assert_eq!(*offsets.borrow(), [0, 17, 27]);
+}
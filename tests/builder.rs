@@ -0,0 +1,29 @@
+use nrbf_rs::value::Value;
+use std::collections::HashMap;
+
+#[test]
+fn object_builder_matches_literal() {
+    let built = Value::object("Point")
+        .library("Geometry")
+        .field("x", Value::I32(1))
+        .field("tags", Value::array(vec![Value::String("a".to_owned())]))
+        .build();
+    let literal = Value::Object(
+        "Point".into(),
+        Some("Geometry".into()),
+        HashMap::from([
+            ("x".into(), Value::I32(1)),
+            (
+                "tags".into(),
+                Value::Array(vec![1], vec![0], vec![Value::String("a".to_owned())]),
+            ),
+        ]),
+    );
+    assert_eq!(built, literal);
+
+    let system: Value = Value::object("System.Empty").into();
+    assert_eq!(
+        system,
+        Value::Object("System.Empty".into(), None, HashMap::new())
+    );
+}
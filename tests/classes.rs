@@ -0,0 +1,421 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    parse_nrbf, parse_nrbf_document, parse_nrbf_with_options, BinaryType, Class, ClassField,
+    ParseErrorKind, ParseOptions, ParseWarning, ParseWarningKind, PrimitiveType,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn system_foo(a: &str) -> Value {
+    Value::Object(
+        "System.Foo".into(),
+        None,
+        HashMap::from([("a".into(), Value::String(a.to_owned()))]),
+    )
+}
+
+#[test]
+fn class_with_id_references_system_class() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        // BinaryArray of two objects.
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(2)
+        // SystemClassWithMembers.
+        .u8(2)
+        .i32(2)
+        .lps("System.Foo")
+        .i32(1)
+        .lps("a")
+        .object_string(3, "x")
+        // ClassWithId reusing the system class.
+        .u8(1)
+        .i32(4)
+        .i32(2)
+        .object_string(5, "y")
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(vec![2], vec![0], vec![system_foo("x"), system_foo("y")])
+    );
+}
+
+/// An array of three `System.Foo` instances. The class is defined by the first one, the others
+/// are `ClassWithId` records referencing the first, then either the class or the second instance.
+fn class_with_id_chain(last_class_id: i32) -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(3)
+        .u8(2)
+        .u8(2)
+        .i32(2)
+        .lps("System.Foo")
+        .i32(1)
+        .lps("a")
+        .object_string(3, "x")
+        .u8(1)
+        .i32(4)
+        .i32(2)
+        .object_string(5, "y")
+        .u8(1)
+        .i32(6)
+        .i32(last_class_id)
+        .object_string(7, "z")
+        .message_end()
+        .build()
+}
+
+#[test]
+fn class_with_id_chain_shares_one_class() {
+    let bytes = class_with_id_chain(2);
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let objects = vec![system_foo("x"), system_foo("y"), system_foo("z")];
+    assert_eq!(value, Value::Array(vec![3], vec![0], objects));
+
+    let bytes = class_with_id_chain(4);
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ClassWithIdTargetNotAClass {
+            object_id: 4,
+            referenced_by_offset: 77
+        }
+    ));
+}
+
+#[test]
+fn class_with_id_references_undefined_class() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(2, "padding")
+        .u8(1)
+        .i32(1)
+        .i32(9)
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ClassNotDefined {
+            class_id: 9,
+            referenced_by_offset: 30
+        }
+    ));
+}
+
+#[test]
+fn object_typed_members_are_records() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        // ClassWithMembersAndTypes with two members of BinaryType::Object.
+        .u8(5)
+        .i32(1)
+        .lps("C")
+        .i32(2)
+        .lps("a")
+        .lps("b")
+        .u8(2)
+        .u8(2)
+        .i32(2)
+        .object_string(3, "x")
+        .u8(10)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Object(
+            "C".into(),
+            Some("lib".into()),
+            HashMap::from([
+                ("a".into(), Value::String("x".to_owned())),
+                ("b".into(), Value::Null),
+            ])
+        )
+    );
+}
+
+#[test]
+fn class_without_members_is_an_empty_object() {
+    // SystemClassWithMembersAndTypes without members.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("System.Empty")
+        .i32(0)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::Object("System.Empty".into(), None, HashMap::new())
+    );
+}
+
+#[test]
+fn negative_member_count_is_an_error() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("System.Foo")
+        .i32(-1)
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::InvalidMemberCount { member_count: -1 }
+    ));
+    assert_eq!(error.offset, 37);
+}
+
+#[test]
+fn binary_type_outside_the_spec_is_an_error() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("System.Foo")
+        .i32(1)
+        .lps("a")
+        .u8(8)
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnexpectedEnumValue {
+            enum_name: "BinaryType",
+            byte: 8
+        }
+    ));
+}
+
+#[test]
+fn class_with_id_references_known_class() {
+    // ClassWithId of class 5, which is not defined in the message.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(1)
+        .i32(1)
+        .i32(5)
+        .i32(7)
+        .object_string(2, "a")
+        .message_end()
+        .build();
+
+    let class = Class::new(
+        "Point",
+        Some("Geometry".to_owned()),
+        vec![
+            ClassField::primitive("x", PrimitiveType::Int32),
+            ClassField::untyped("label"),
+        ],
+    );
+    let options = ParseOptions {
+        known_classes: vec![(5, class)],
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    assert_eq!(
+        value,
+        Value::Object(
+            "Point".into(),
+            Some("Geometry".into()),
+            HashMap::from([
+                ("x".into(), Value::I32(7)),
+                ("label".into(), Value::String("a".to_owned())),
+            ]),
+        )
+    );
+}
+
+#[test]
+fn undeclared_library_is_an_error_in_strict_mode() {
+    // ClassWithMembers of library 2, which is not declared.
+    let members = StreamBuilder::new()
+        .header(1)
+        .u8(3)
+        .i32(1)
+        .lps("C")
+        .i32(1)
+        .lps("a")
+        .i32(2)
+        .object_string(3, "x")
+        .message_end()
+        .build();
+    let typed = StreamBuilder::new()
+        .header(1)
+        .primitive_class(1, 8, 2)
+        .i32(7)
+        .message_end()
+        .build();
+
+    let document = parse_nrbf_document(&mut members.as_slice(), ParseOptions::default()).unwrap();
+    assert!(matches!(document.root, Value::Object(_, None, _)));
+    let warning = ParseWarning {
+        kind: ParseWarningKind::UnknownLibrary { library_id: 2 },
+        offset: 34,
+    };
+    assert_eq!(document.warnings, [warning]);
+
+    for bytes in [members, typed] {
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let error = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::UnknownLibrary { library_id: 2 }
+        ));
+    }
+}
+
+#[test]
+fn every_binary_type_byte_outside_the_spec_is_an_error() {
+    for byte in 8..=u8::MAX {
+        let bytes = StreamBuilder::new()
+            .header(1)
+            .u8(4)
+            .i32(1)
+            .lps("System.Foo")
+            .i32(1)
+            .lps("a")
+            .u8(byte)
+            .message_end()
+            .build();
+
+        let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+        assert!(
+            matches!(
+                error.kind,
+                ParseErrorKind::UnexpectedEnumValue {
+                    enum_name: "BinaryType",
+                    byte: b
+                } if b == byte
+            ),
+            "{byte}: {error}"
+        );
+    }
+}
+
+#[test]
+fn document_exposes_class_definitions() {
+    // ClassWithMembersAndTypes with an Int32 member `n` and a member `next` of class `Node`.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .u8(5)
+        .i32(1)
+        .lps("Node")
+        .i32(2)
+        .lps("n")
+        .lps("next")
+        .u8(0)
+        .u8(4)
+        .u8(8)
+        .lps("Node")
+        .i32(2)
+        .i32(2)
+        .i32(7)
+        .u8(10)
+        .message_end()
+        .build();
+
+    let document = parse_nrbf_document(&mut bytes.as_slice(), ParseOptions::default()).unwrap();
+    let class = document.class_of(&document.root).unwrap();
+    assert_eq!((class.name(), class.library_name()), ("Node", Some("lib")));
+    let [n, next] = class.fields() else {
+        panic!("expected two fields, got {:?}", class.fields());
+    };
+    assert_eq!(n.name(), "n");
+    assert_eq!(n.binary_type(), Some(BinaryType::Primitive));
+    assert_eq!(n.primitive_type(), Some(PrimitiveType::Int32));
+    assert_eq!(next.name(), "next");
+    assert_eq!(next.binary_type(), Some(BinaryType::Class));
+    assert_eq!(next.class_name(), Some("Node"));
+    assert_eq!(document.class_of(&Value::Null).map(Class::name), None);
+}
+
+#[test]
+fn names_are_shared_between_instances() {
+    // An array of two `System.Foo` instances and a `System.Bar`, all with a member `a`.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(3)
+        .u8(2)
+        .u8(2)
+        .i32(2)
+        .lps("System.Foo")
+        .i32(1)
+        .lps("a")
+        .object_string(3, "x")
+        .u8(1)
+        .i32(4)
+        .i32(2)
+        .object_string(5, "y")
+        .u8(2)
+        .i32(6)
+        .lps("System.Bar")
+        .i32(1)
+        .lps("a")
+        .object_string(7, "z")
+        .message_end()
+        .build();
+
+    let parse_names = |intern_strings| {
+        let options = ParseOptions {
+            intern_strings,
+            ..Default::default()
+        };
+        let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+        let Value::Array(_, _, objects) = value else {
+            panic!("Expected an array, got {value:?}");
+        };
+        objects
+            .into_iter()
+            .map(|object| match object {
+                Value::Object(class_name, _, members) => {
+                    let (member_name, _) = members.get_key_value("a").unwrap();
+                    (class_name, member_name.clone())
+                }
+                _ => panic!("Expected an object, got {object:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let names = parse_names(false);
+    assert!(Arc::ptr_eq(&names[0].0, &names[1].0));
+    assert!(Arc::ptr_eq(&names[0].1, &names[1].1));
+    assert!(!Arc::ptr_eq(&names[0].1, &names[2].1));
+
+    let interned = parse_names(true);
+    assert!(Arc::ptr_eq(&interned[0].1, &interned[2].1));
+}
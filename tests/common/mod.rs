@@ -0,0 +1,122 @@
+// Each test crate only uses a subset of the helpers.
+#![allow(dead_code)]
+
+/// Builds NRBF byte streams for tests.
+#[derive(Default, Clone)]
+pub struct StreamBuilder {
+    bytes: Vec<u8>,
+}
+
+impl StreamBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn u8(self, value: u8) -> Self {
+        self.bytes(&[value])
+    }
+
+    pub fn i32(self, value: i32) -> Self {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    /// A length prefixed string.
+    pub fn lps(mut self, value: &str) -> Self {
+        let mut length = value.len();
+        loop {
+            let byte = (length & 0b01111111) as u8;
+            length >>= 7;
+            if length == 0 {
+                self.bytes.push(byte);
+                break;
+            }
+            self.bytes.push(byte | 0b10000000);
+        }
+        self.bytes(value.as_bytes())
+    }
+
+    pub fn header(self, root_id: i32) -> Self {
+        self.u8(0).i32(root_id).i32(-1).i32(1).i32(0)
+    }
+
+    pub fn object_string(self, id: i32, value: &str) -> Self {
+        self.u8(6).i32(id).lps(value)
+    }
+
+    pub fn library(self, id: i32, name: &str) -> Self {
+        self.u8(12).i32(id).lps(name)
+    }
+
+    /// A `ClassWithMembersAndTypes` record of a class with a single primitive member `v`. The
+    /// member's value must follow.
+    pub fn primitive_class(self, id: i32, primitive_type: u8, library_id: i32) -> Self {
+        self.u8(5)
+            .i32(id)
+            .lps("C")
+            .i32(1)
+            .lps("v")
+            .u8(0)
+            .u8(primitive_type)
+            .i32(library_id)
+    }
+
+    pub fn message_end(self) -> Self {
+        self.u8(11)
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// A complete stream whose root is a class with a single primitive member `v`, encoded as `bytes`.
+pub fn primitive_member(primitive_type: u8, bytes: &[u8]) -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .primitive_class(1, primitive_type, 2)
+        .bytes(bytes)
+        .message_end()
+        .build()
+}
+
+/// A message whose root has two members referencing the same object, which in turn has two
+/// members referencing the next one, and so on. It stands for 2^`depth` values once resolved.
+pub fn doubling_references(depth: i32) -> Vec<u8> {
+    let mut builder = StreamBuilder::new()
+        .header(1)
+        .library(2, "Lib")
+        .u8(5)
+        .i32(1)
+        .lps("Node")
+        .i32(2)
+        .lps("a")
+        .lps("b")
+        .u8(2)
+        .u8(2)
+        .i32(2);
+    for id in 3..3 + depth {
+        builder = builder.u8(9).i32(id).u8(9).i32(id).u8(1).i32(id).i32(1);
+    }
+    builder.u8(10).u8(10).message_end().build()
+}
+
+/// A message of `length` objects, each of which has a member `next` referencing the next one.
+pub fn reference_chain(length: i32) -> Vec<u8> {
+    let mut builder = StreamBuilder::new()
+        .header(1)
+        .u8(2)
+        .i32(1)
+        .lps("Node")
+        .i32(1)
+        .lps("next");
+    for id in 2..=length {
+        builder = builder.u8(9).i32(id).u8(1).i32(id).i32(1);
+    }
+    builder.u8(10).message_end().build()
+}
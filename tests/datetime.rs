@@ -0,0 +1,66 @@
+mod common;
+
+use common::primitive_member;
+use nrbf_rs::parse_nrbf;
+use nrbf_rs::value::{DateTimeKind, Value};
+
+/// 2000-01-01T00:00:00.
+const Y2K_TICKS: u64 = 630_822_816_000_000_000;
+
+fn decode(bits: u64) -> Value {
+    let stream = primitive_member(13, &bits.to_le_bytes());
+    let root = parse_nrbf(&mut stream.as_slice()).unwrap();
+    root.members().unwrap()["v"].clone()
+}
+
+#[test]
+fn date_time_kind_is_split_from_ticks() {
+    let ticks = Y2K_TICKS as i64;
+    assert_eq!(
+        decode(Y2K_TICKS),
+        Value::DateTime(ticks, DateTimeKind::Unspecified)
+    );
+    assert_eq!(
+        decode(Y2K_TICKS | 1 << 62),
+        Value::DateTime(ticks, DateTimeKind::Utc)
+    );
+    assert_eq!(
+        decode(Y2K_TICKS | 2 << 62),
+        Value::DateTime(ticks, DateTimeKind::Local)
+    );
+    assert_eq!(
+        decode(Y2K_TICKS | 3 << 62),
+        Value::DateTime(ticks, DateTimeKind::Local)
+    );
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn date_time_converts_to_chrono() {
+    use chrono::{TimeZone, Utc};
+
+    let value = decode((Y2K_TICKS + 1_234_567) | 1 << 62);
+    let expected = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()
+        + chrono::TimeDelta::nanoseconds(123_456_700);
+    assert_eq!(value.as_datetime_utc(), Some(expected));
+
+    let out_of_range = Value::DateTime(3_155_378_976_000_000_000, DateTimeKind::Utc);
+    assert_eq!(out_of_range.as_datetime_utc(), None);
+    assert_eq!(Value::I64(0).as_datetime_utc(), None);
+}
+
+#[test]
+fn integer_ticks_are_reinterpreted_as_date_time() {
+    let date_time = Value::DateTime(Y2K_TICKS as i64, DateTimeKind::Unspecified);
+    assert_eq!(
+        Value::U64(Y2K_TICKS).reinterpret_ticks_as_datetime(),
+        Some(date_time.clone())
+    );
+    assert_eq!(
+        Value::I64(Y2K_TICKS as i64).reinterpret_ticks_as_datetime(),
+        Some(date_time)
+    );
+    assert_eq!(Value::I64(-1).reinterpret_ticks_as_datetime(), None);
+    assert_eq!(Value::U64(u64::MAX).reinterpret_ticks_as_datetime(), None);
+    assert_eq!(Value::I32(0).reinterpret_ticks_as_datetime(), None);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "derive")]
+
+use nrbf_rs::value::Value;
+use nrbf_rs::FromNrbf;
+
+#[derive(FromNrbf, Debug, PartialEq)]
+struct Circle {
+    radius: f64,
+}
+
+#[derive(FromNrbf, Debug, PartialEq)]
+struct Square {
+    #[nrbf(rename = "<Side>k__BackingField")]
+    side: i32,
+}
+
+#[derive(FromNrbf, Debug, PartialEq)]
+enum Shape {
+    Circle(Circle),
+    #[nrbf(rename = "Game.Shapes.Square")]
+    Square(Square),
+}
+
+#[test]
+fn enum_variant_is_chosen_by_class_name() {
+    let shapes = Value::array(vec![
+        Value::object("Circle")
+            .field("radius", Value::F64(0.5))
+            .build(),
+        Value::object("Game.Shapes.Square")
+            .field("<Side>k__BackingField", Value::I32(2))
+            .build(),
+    ]);
+    assert_eq!(
+        Vec::<Shape>::try_from(&shapes),
+        Ok(vec![
+            Shape::Circle(Circle { radius: 0.5 }),
+            Shape::Square(Square { side: 2 })
+        ])
+    );
+
+    let triangle = Value::object("Triangle").build();
+    assert_eq!(
+        Shape::try_from(&triangle),
+        Err("Unexpected class Triangle".to_owned())
+    );
+    let circle = Value::object("Circle").build();
+    assert_eq!(
+        Shape::try_from(&circle),
+        Err("Circle: Missing member radius".to_owned())
+    );
+    assert_eq!(
+        Shape::try_from(&Value::Null),
+        Err("Expected Object; Got Null".to_owned())
+    );
+}
@@ -0,0 +1,103 @@
+use nrbf_rs::value::Value;
+use std::collections::HashMap;
+
+fn object(class_name: &str, member: &str, value: Value) -> Value {
+    Value::Object(
+        class_name.into(),
+        None,
+        HashMap::from([(member.into(), value)]),
+    )
+}
+
+#[test]
+fn strings_are_quoted_and_escaped() {
+    let value = object("Note", "text", Value::String("a \"b\"\n{c}".to_owned()));
+    assert_eq!(
+        value.to_string(),
+        "Note {\n  text: \"a \\\"b\\\"\\n{c}\",\n}"
+    );
+}
+
+#[test]
+fn names_are_quoted_only_when_special() {
+    let generic = "System.Collections.Generic.List`1[[System.Int32, mscorlib]]";
+    let value = object(generic, "<Items>k__BackingField", Value::Null);
+    assert_eq!(
+        value.to_string(),
+        format!("{generic} {{\n  <Items>k__BackingField: Null,\n}}")
+    );
+
+    let value = object("Odd\nName", "}", Value::Null);
+    assert_eq!(value.to_string(), "\"Odd\\nName\" {\n  \"}\": Null,\n}");
+}
+
+#[test]
+fn alternate_format_is_a_single_line() {
+    let array = Value::Array(vec![2], vec![0], vec![Value::I32(1), Value::Null]);
+    let value = object("Point", "xs", array);
+    assert_eq!(format!("{value:#}"), "Point { xs: [1i32, Null] }");
+    assert_eq!(value.to_compact_string(), "Point { xs: [1i32, Null] }");
+
+    let empty = Value::Object("Empty".into(), None, HashMap::new());
+    assert_eq!(empty.to_compact_string(), "Empty {}");
+
+    let method_return = Value::MethodReturn {
+        return_value: Some(Box::new(Value::Bool(true))),
+        call_context: None,
+        args: Some(vec![]),
+    };
+    assert_eq!(
+        method_return.to_compact_string(),
+        "MethodReturn { return_value: true, args: [] }"
+    );
+    assert_eq!(
+        method_return.to_string(),
+        "MethodReturn {\n  return_value: true,\n  args: [\n  ],\n}"
+    );
+}
+
+#[test]
+fn limited_display_elides_deep_and_long_values() {
+    let numbers = Value::array((0..5).map(Value::I32).collect());
+    let value = object("Point", "xs", numbers);
+
+    assert_eq!(
+        format!("{:#}", value.display_limited(2, 3)),
+        "Point { xs: [0i32, 1i32, 2i32, …] }"
+    );
+    assert_eq!(
+        format!("{:#}", value.display_limited(1, 3)),
+        "Point { xs: […] }"
+    );
+    assert_eq!(format!("{:#}", value.display_limited(0, 3)), "Point {…}");
+    assert_eq!(
+        value.display_limited(2, 1).to_string(),
+        "Point {\n  xs: [\n    0i32,\n    …\n  ],\n}"
+    );
+    assert_eq!(
+        format!("{:#}", Value::Bytes(vec![1, 2, 3]).display_limited(1, 2)),
+        "[1u8, 2u8, …]"
+    );
+
+    let limited = value.display_limited(usize::MAX, usize::MAX);
+    assert_eq!(limited.to_string(), value.to_string());
+    let empty = Value::array(vec![]);
+    assert_eq!(format!("{:#}", empty.display_limited(0, 0)), "[]");
+}
+
+#[test]
+fn multidimensional_arrays_print_as_rows() {
+    let values = (0..6).map(Value::I32).collect();
+    let matrix = Value::Array(vec![2, 3], vec![0, 0], values);
+    assert_eq!(
+        format!("{matrix:#}"),
+        "[[0i32, 1i32, 2i32], [3i32, 4i32, 5i32]]"
+    );
+    assert_eq!(format!("{:#}", matrix.display_limited(1, 6)), "[[…], […]]");
+
+    let offset = Value::Array(vec![2], vec![5], vec![Value::I32(1), Value::I32(2)]);
+    assert_eq!(format!("{offset:#}"), "from [5] [1i32, 2i32]");
+
+    let empty_rows = Value::Array(vec![2, 0], vec![0, 0], vec![]);
+    assert_eq!(format!("{empty_rows:#}"), "[[], []]");
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use common::{doubling_references, reference_chain, StreamBuilder};
+use nrbf_rs::ffi::{
+    nrbf_free_string, nrbf_parse, NRBF_INVALID_ARGUMENT, NRBF_OK, NRBF_PARSE_ERROR,
+};
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+#[test]
+fn parse_to_json_string() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+
+    let mut json: *mut c_char = ptr::null_mut();
+    let code = unsafe { nrbf_parse(bytes.as_ptr(), bytes.len(), &mut json) };
+    assert_eq!(code, NRBF_OK);
+    assert_eq!(unsafe { CStr::from_ptr(json) }.to_str(), Ok("\"root\""));
+    unsafe { nrbf_free_string(json) };
+}
+
+#[test]
+fn invalid_input_is_an_error_code() {
+    let mut json: *mut c_char = ptr::null_mut();
+    let truncated = StreamBuilder::new().header(1).build();
+    let code = unsafe { nrbf_parse(truncated.as_ptr(), truncated.len(), &mut json) };
+    assert_eq!(code, NRBF_PARSE_ERROR);
+    assert!(json.is_null());
+
+    let code = unsafe { nrbf_parse(ptr::null(), 0, &mut json) };
+    assert_eq!(code, NRBF_INVALID_ARGUMENT);
+    let code = unsafe { nrbf_parse(truncated.as_ptr(), truncated.len(), ptr::null_mut()) };
+    assert_eq!(code, NRBF_INVALID_ARGUMENT);
+    unsafe { nrbf_free_string(ptr::null_mut()) };
+}
+
+#[test]
+fn hostile_input_is_an_error_code() {
+    for bytes in [doubling_references(40), reference_chain(200_000)] {
+        let mut json: *mut c_char = ptr::null_mut();
+        let code = unsafe { nrbf_parse(bytes.as_ptr(), bytes.len(), &mut json) };
+        assert_eq!(code, NRBF_PARSE_ERROR);
+        assert!(json.is_null());
+    }
+}
@@ -0,0 +1,48 @@
+// Stable properties of the example dumps, which are real-world save files.
+
+use nrbf_rs::value::Value;
+use nrbf_rs::{parse_nrbf_document, ParseOptions, ParsedDocument};
+use std::fs::File;
+use std::io::BufReader;
+
+fn parse(path: &str) -> ParsedDocument {
+    let mut stream = BufReader::new(File::open(path).unwrap());
+    parse_nrbf_document(&mut stream, ParseOptions::default()).unwrap()
+}
+
+fn member<'a>(value: &'a Value, name: &str) -> &'a Value {
+    &value.members().unwrap()[name]
+}
+
+#[test]
+fn batim() {
+    let document = parse("examples/batim.dump");
+    let root = &document.root;
+    assert_eq!(root.class_name(), Some("TMG.Data.GameData"));
+    assert_eq!(root.library_name(), Some("Assembly-CSharp"));
+    assert_eq!(root.members().unwrap().len(), 13);
+    assert_eq!(document.objects.len(), 164);
+
+    assert_eq!(member(root, "ContinueIndex"), &Value::I32(5));
+    let bacon_soup = member(member(root, "CH4AchievementData"), "BaconSoup");
+    assert_eq!(member(bacon_soup, "_size"), &Value::I32(3));
+    let items = Vec::<i32>::try_from(member(bacon_soup, "_items")).unwrap();
+    assert_eq!(items, vec![6, 15, 17, 0]);
+}
+
+#[test]
+fn dorfromantik() {
+    let document = parse("examples/dorfromantik.dump");
+    let root = &document.root;
+    assert_eq!(root.class_name(), Some("SaveGameData_003"));
+    assert_eq!(root.members().unwrap().len(), 33);
+    assert_eq!(document.objects.len(), 116637);
+
+    assert_eq!(member(root, "level"), &Value::I32(312));
+    assert_eq!(member(root, "score"), &Value::I32(949490));
+    assert_eq!(
+        member(root, "initialVersion"),
+        &Value::String("1.0.7".to_owned())
+    );
+    assert_eq!(member(member(root, "tiles"), "_size"), &Value::I32(11406));
+}
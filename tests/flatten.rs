@@ -0,0 +1,27 @@
+use nrbf_rs::value::Value;
+
+#[test]
+fn flatten_paths() {
+    let sword = Value::object("Item")
+        .field("Name", Value::String("Sword".to_owned()))
+        .build();
+    let player = Value::object("Player")
+        .field("Inventory", Value::array(vec![sword, Value::Null]))
+        .field("Level", Value::I32(3))
+        .field("Icon", Value::Bytes(vec![7]))
+        .field("Guild", Value::Reference(4))
+        .build();
+
+    let string = Value::String("Sword".to_owned());
+    assert_eq!(
+        player.flatten(),
+        vec![
+            ("Guild".to_owned(), &Value::Reference(4)),
+            ("Icon[0]".to_owned(), &Value::U8(7)),
+            ("Inventory[0].Name".to_owned(), &string),
+            ("Inventory[1]".to_owned(), &Value::Null),
+            ("Level".to_owned(), &Value::I32(3)),
+        ]
+    );
+    assert_eq!(string.flatten(), vec![(String::new(), &string)]);
+}
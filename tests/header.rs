@@ -0,0 +1,171 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    is_nrbf, parse_nrbf, parse_nrbf_document, parse_nrbf_with_options, ParseErrorKind,
+    ParseOptions, ParseWarning, ParseWarningKind, RecordType,
+};
+
+#[test]
+fn document_exposes_header() {
+    let stream = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+    let document = parse_nrbf_document(&mut stream.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(document.root_id, 1);
+    assert_eq!(document.header_id, -1);
+    assert_eq!((document.major_version, document.minor_version), (1, 0));
+    assert_eq!(
+        document.root_record_type,
+        Some(RecordType::BinaryObjectString)
+    );
+}
+
+#[test]
+fn root_record_type_of_an_array() {
+    // ArraySinglePrimitive of two Int32 values.
+    let stream = StreamBuilder::new()
+        .header(1)
+        .u8(15)
+        .i32(1)
+        .i32(2)
+        .u8(8)
+        .i32(3)
+        .i32(4)
+        .message_end()
+        .build();
+    let document = parse_nrbf_document(&mut stream.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(
+        document.root_record_type,
+        Some(RecordType::ArraySinglePrimitive)
+    );
+}
+
+#[test]
+fn unsupported_version_is_an_error_in_strict_mode() {
+    let stream = StreamBuilder::new()
+        .u8(0)
+        .i32(1)
+        .i32(-1)
+        .i32(2)
+        .i32(3)
+        .build();
+    let options = ParseOptions {
+        strict_version: true,
+        ..Default::default()
+    };
+    let error = parse_nrbf_with_options(&mut stream.as_slice(), options).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnsupportedVersion { major: 2, minor: 3 }
+    ));
+}
+
+#[test]
+fn unsupported_version_is_a_warning() {
+    let stream = StreamBuilder::new()
+        .u8(0)
+        .i32(1)
+        .i32(-1)
+        .i32(1)
+        .i32(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+    let document = parse_nrbf_document(&mut stream.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(document.root, Value::String("root".to_owned()));
+    assert_eq!((document.major_version, document.minor_version), (1, 1));
+    assert_eq!(
+        document.warnings,
+        [ParseWarning {
+            kind: ParseWarningKind::UnsupportedVersion { major: 1, minor: 1 },
+            offset: 17,
+        }]
+    );
+}
+
+#[test]
+fn missing_header_is_an_error() {
+    let empty = StreamBuilder::new().build();
+    let no_header = StreamBuilder::new().library(2, "lib").message_end().build();
+    let truncated = StreamBuilder::new().library(2, "lib").build();
+
+    for stream in [empty, no_header, truncated] {
+        let error = parse_nrbf(&mut stream.as_slice()).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingSerializationHeader
+        ));
+    }
+}
+
+#[test]
+fn truncated_header_is_reported_as_such() {
+    let record_type_only = StreamBuilder::new().u8(0).build();
+    let partial = StreamBuilder::new().u8(0).i32(1).i32(-1).u8(1).build();
+
+    for stream in [record_type_only, partial] {
+        let error = parse_nrbf(&mut stream.as_slice()).unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::TruncatedHeader));
+    }
+
+    // Truncation after the header is an I/O error of the body.
+    let body = StreamBuilder::new().header(1).u8(6).build();
+    let error = parse_nrbf(&mut body.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+}
+
+#[test]
+fn sniffs_nrbf_headers() {
+    let message = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "x")
+        .message_end();
+    assert!(is_nrbf(&message.build()));
+    assert!(is_nrbf(&StreamBuilder::new().header(1).build()));
+
+    let header = StreamBuilder::new().header(1).build();
+    assert!(!is_nrbf(&header[..16]));
+    assert!(!is_nrbf(&[]));
+    assert!(!is_nrbf(b"PK\x03\x04 not a message at all"));
+
+    let version_2 = StreamBuilder::new()
+        .u8(0)
+        .i32(1)
+        .i32(-1)
+        .i32(2)
+        .i32(0)
+        .build();
+    assert!(!is_nrbf(&version_2));
+}
+
+#[test]
+fn root_can_be_resolved_from_header_id() {
+    // A header with root id 0 and header id 2, defining string 2.
+    let stream = StreamBuilder::new()
+        .u8(0)
+        .i32(0)
+        .i32(2)
+        .i32(1)
+        .i32(0)
+        .object_string(2, "entry")
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut stream.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ObjectNotDefined { object_id: 0 }
+    ));
+
+    let options = ParseOptions {
+        root_from_header_id: true,
+        ..Default::default()
+    };
+    let document = parse_nrbf_document(&mut stream.as_slice(), options).unwrap();
+    assert_eq!(document.root.as_str(), Some("entry"));
+    assert_eq!((document.root_id, document.header_id), (2, 2));
+}
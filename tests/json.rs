@@ -0,0 +1,288 @@
+#![cfg(feature = "json")]
+
+mod common;
+
+use common::{doubling_references, reference_chain, StreamBuilder};
+use nrbf_rs::json::{
+    from_json, parse_nrbf_to_json, parse_nrbf_to_typed_json, to_json, to_typed_json, JsonError,
+    ReferenceMode,
+};
+use nrbf_rs::value::{DateTimeKind, Value};
+use nrbf_rs::writer::write_nrbf;
+use nrbf_rs::{parse_nrbf, ParseErrorKind};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+
+/// A `Root` object whose members `a` and `b` both reference `Shared` object 3. If `cyclic`, the
+/// shared object references the root instead of holding a primitive.
+fn shared_object_stream(cyclic: bool) -> Vec<u8> {
+    let builder = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .u8(5)
+        .i32(1)
+        .lps("Root")
+        .i32(2)
+        .lps("a")
+        .lps("b")
+        .u8(2)
+        .u8(2)
+        .i32(2)
+        .u8(5)
+        .i32(3)
+        .lps("Shared")
+        .i32(1)
+        .lps("v");
+    let builder = if cyclic {
+        // A member of BinaryType::Object referencing the root.
+        builder.u8(2).i32(2).u8(9).i32(1)
+    } else {
+        // A member of primitive type Int32.
+        builder.u8(0).u8(8).i32(2).i32(7)
+    };
+    builder.u8(9).i32(3).message_end().build()
+}
+
+#[test]
+fn inline_duplicates_shared_objects() {
+    let stream = shared_object_stream(false);
+    let json = parse_nrbf_to_json(&mut stream.as_slice(), ReferenceMode::Inline).unwrap();
+    let shared = json!({"$class": "Shared", "$library": "lib", "v": 7});
+    assert_eq!(
+        json,
+        json!({"$class": "Root", "$library": "lib", "a": shared, "b": shared})
+    );
+}
+
+#[test]
+fn by_id_references_shared_objects() {
+    let stream = shared_object_stream(false);
+    let json = parse_nrbf_to_json(&mut stream.as_slice(), ReferenceMode::ById).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "root": {"$ref": 1},
+            "objects": {
+                "1": {"$class": "Root", "$library": "lib", "a": {"$ref": 3}, "b": {"$ref": 3}},
+                "3": {"$class": "Shared", "$library": "lib", "v": 7},
+            },
+        })
+    );
+}
+
+#[test]
+fn inline_rejects_cycles() {
+    let stream = shared_object_stream(true);
+    let result = parse_nrbf_to_json(&mut stream.as_slice(), ReferenceMode::Inline);
+    assert!(matches!(result, Err(JsonError::Cycle(1))));
+
+    let stream = shared_object_stream(true);
+    let json = parse_nrbf_to_json(&mut stream.as_slice(), ReferenceMode::ById).unwrap();
+    assert_eq!(
+        json["objects"]["3"],
+        json!({"$class": "Shared", "$library": "lib", "v": {"$ref": 1}})
+    );
+}
+
+#[test]
+fn inline_is_limited_like_parsing() {
+    // Inlining these would make exponentially many copies, or recurse 200 000 levels deep.
+    let copies = doubling_references(40);
+    let result = parse_nrbf_to_json(&mut copies.as_slice(), ReferenceMode::Inline);
+    assert!(matches!(
+        result,
+        Err(JsonError::Parse(error)) if matches!(error.kind, ParseErrorKind::TooManyCopiedBytes { .. })
+    ));
+    let chain = reference_chain(200_000);
+    let result = parse_nrbf_to_json(&mut chain.as_slice(), ReferenceMode::Inline);
+    assert!(matches!(
+        result,
+        Err(JsonError::Parse(error)) if matches!(error.kind, ParseErrorKind::TooDeep { .. })
+    ));
+
+    // Converting back by id inlines the references the same way.
+    let json = parse_nrbf_to_json(&mut copies.as_slice(), ReferenceMode::ById).unwrap();
+    assert_eq!(json["objects"]["3"]["a"], json!({"$ref": 4}));
+    let result = from_json(&json, ReferenceMode::ById);
+    assert!(matches!(
+        result,
+        Err(JsonError::Parse(error)) if matches!(error.kind, ParseErrorKind::TooManyCopiedBytes { .. })
+    ));
+}
+
+#[test]
+fn values_convert_to_json() {
+    let value = Value::object("Point")
+        .field("x", Value::F64(f64::NAN))
+        .field("name", Value::String("a".to_owned()))
+        .field("next", Value::Reference(2))
+        .field("tags", Value::array(vec![Value::Null, Value::Bool(true)]))
+        .build();
+    assert_eq!(
+        serde_json::Value::from(&value),
+        json!({
+            "$class": "Point",
+            "x": null,
+            "name": "a",
+            "next": {"$ref": 2},
+            "tags": [null, true],
+        })
+    );
+}
+
+#[test]
+fn edited_json_is_written_back() {
+    let stream = shared_object_stream(false);
+    for mode in [ReferenceMode::Inline, ReferenceMode::ById] {
+        let mut json = parse_nrbf_to_json(&mut stream.as_slice(), mode).unwrap();
+        let value = from_json(&json, mode).unwrap();
+        let mut written = Vec::new();
+        write_nrbf(&value, &mut written).unwrap();
+        let expected = parse_nrbf(&mut stream.as_slice()).unwrap();
+        assert_eq!(parse_nrbf(&mut written.as_slice()).unwrap(), expected);
+
+        // Inlined objects are edited one copy at a time, shared objects in all places at once.
+        let b = match mode {
+            ReferenceMode::Inline => {
+                json["a"]["v"] = json!(8);
+                7
+            }
+            ReferenceMode::ById => {
+                json["objects"]["3"]["v"] = json!(8);
+                8
+            }
+        };
+        let value = from_json(&json, mode).unwrap();
+        let shared = Value::object("Shared").library("lib");
+        let edited = Value::object("Root")
+            .library("lib")
+            .field("a", shared.clone().field("v", Value::I32(8)).build())
+            .field("b", shared.field("v", Value::I32(b)).build())
+            .build();
+        assert_eq!(value, edited);
+    }
+}
+
+#[test]
+fn json_deserializes_to_values() {
+    let value: Value = serde_json::from_value(json!({
+        "$class": "MethodReturn",
+        "return_value": {"ticks": 5, "kind": "Utc"},
+        "call_context": null,
+        "args": [1, 5_000_000_000_i64, 1.5, "x"],
+    }))
+    .unwrap();
+    assert_eq!(
+        value,
+        Value::MethodReturn {
+            return_value: Some(Box::new(Value::DateTime(5, DateTimeKind::Utc))),
+            call_context: None,
+            args: Some(vec![
+                Value::I32(1),
+                Value::I64(5_000_000_000),
+                Value::F64(1.5),
+                Value::String("x".to_owned()),
+            ]),
+        }
+    );
+    assert_eq!(
+        serde_json::Value::from(&value)["args"][1],
+        json!(5_000_000_000_i64)
+    );
+
+    let result = serde_json::from_value::<Value>(json!({"x": 1}));
+    assert!(result.is_err());
+    let result = from_json(&json!({"objects": {}}), ReferenceMode::ById);
+    assert!(matches!(result, Err(JsonError::Deserialize(_))));
+}
+
+/// An object with a member of each variant whose type plain JSON does not preserve.
+fn typed_object() -> Value {
+    Value::object("Typed")
+        .field("u8", Value::U8(1))
+        .field("u32", Value::U32(2))
+        .field("u64", Value::U64(3))
+        .field("i8", Value::I8(-4))
+        .field("i64", Value::I64(5))
+        .field("f32", Value::F32(0.5))
+        .field("nan", Value::F64(f64::NAN))
+        .field("char", Value::Char('c'))
+        .field("time_span", Value::TimeSpan(6))
+        .field("decimal", Value::Decimal("7.50".to_owned()))
+        .field("bytes", Value::Bytes(vec![0, 255]))
+        .field(
+            "matrix",
+            Value::Array(vec![1, 2], vec![1, 0], vec![Value::U8(8), Value::U8(9)]),
+        )
+        .field(
+            "offset",
+            Value::Array(vec![1], vec![-2], vec![Value::I32(11)]),
+        )
+        .field("i32", Value::I32(10))
+        .build()
+}
+
+#[test]
+fn typed_json_keeps_types() {
+    let value = typed_object();
+    let objects = HashMap::new();
+    let json = to_typed_json(&value, &objects, ReferenceMode::Inline).unwrap();
+    assert_eq!(json["u8"], json!({"$type": "U8", "value": 1}));
+    assert_eq!(json["nan"], json!({"$type": "F64", "value": "NaN"}));
+    assert_eq!(
+        json["matrix"],
+        json!({
+            "$type": "Array",
+            "lengths": [1, 2],
+            "lower_bounds": [1, 0],
+            "values": [{"$type": "U8", "value": 8}, {"$type": "U8", "value": 9}],
+        })
+    );
+    assert_eq!(json["i32"], json!(10));
+    assert!(from_json(&json, ReferenceMode::Inline)
+        .unwrap()
+        .eq_bitwise(&value));
+
+    // Plain JSON guesses the types instead.
+    let json = to_json(&value, &objects, ReferenceMode::Inline).unwrap();
+    let guessed = from_json(&json, ReferenceMode::Inline).unwrap();
+    assert_eq!(guessed.members().unwrap()["u8"], Value::I32(1));
+    assert_eq!(
+        guessed.members().unwrap()["char"],
+        Value::String("c".to_owned())
+    );
+}
+
+#[test]
+fn typed_json_round_trips_through_the_writer() {
+    for path in ["examples/batim.dump", "examples/dorfromantik.dump"] {
+        let expected = parse_nrbf(&mut BufReader::new(File::open(path).unwrap())).unwrap();
+        let mut stream = BufReader::new(File::open(path).unwrap());
+        let json = parse_nrbf_to_typed_json(&mut stream, ReferenceMode::ById).unwrap();
+        let value = from_json(&json, ReferenceMode::ById).unwrap();
+
+        let mut written = Vec::new();
+        write_nrbf(&value, &mut written).unwrap();
+        let written = parse_nrbf(&mut written.as_slice()).unwrap();
+        assert!(written.eq_bitwise(&expected), "{path}");
+    }
+}
+
+#[test]
+fn invalid_tagged_values_are_errors() {
+    for json in [
+        json!({"$type": "U8", "value": 256}),
+        json!({"$type": "Char", "value": "ab"}),
+        json!({"$type": "I16", "value": 1}),
+        json!({"$type": "U32"}),
+        json!({"$type": "U32", "value": 1, "extra": 2}),
+        json!({"$type": "Array", "lengths": [-1], "lower_bounds": [0], "values": []}),
+    ] {
+        assert!(
+            serde_json::from_value::<Value>(json.clone()).is_err(),
+            "{json}"
+        );
+    }
+}
@@ -0,0 +1,411 @@
+// Corrupt inputs found by the `parse_nrbf` fuzz target. Each of them must fail with an error (or
+// parse), instead of panicking or exhausting memory.
+
+mod common;
+
+use common::{doubling_references, StreamBuilder};
+use nrbf_rs::value::Value;
+use nrbf_rs::{parse_nrbf, parse_nrbf_with_options, ParseErrorKind, ParseOptions, PrimitiveType};
+
+#[test]
+fn overlong_string_length_is_an_error() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(6)
+        .i32(1)
+        .bytes(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01])
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidStringLength));
+}
+
+#[test]
+fn string_length_above_i32_max_is_an_error() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(6)
+        .i32(1)
+        .bytes(&[0xff, 0xff, 0xff, 0xff, 0x0f])
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidStringLength));
+}
+
+#[test]
+fn reference_cycle_is_an_error() {
+    // SystemClassWithMembers whose only member refers to the object itself.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(2)
+        .i32(1)
+        .lps("System.Foo")
+        .i32(1)
+        .lps("a")
+        .u8(9)
+        .i32(1)
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ReferenceCycle { object_id: 1 }
+    ));
+}
+
+#[test]
+fn array_length_is_limited() {
+    // ArraySingleObject of 100 elements.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(100)
+        .build();
+
+    let options = ParseOptions {
+        max_array_length: Some(10),
+        ..Default::default()
+    };
+    let error = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong {
+            length: 100,
+            limit: 10
+        }
+    ));
+}
+
+#[test]
+fn primitive_arrays_are_bounded_by_the_input() {
+    // ArraySinglePrimitive of 2,000,000 Doubles, above the default limit of array lengths.
+    let length = 2_000_000;
+    let mut builder = StreamBuilder::new()
+        .header(1)
+        .u8(15)
+        .i32(1)
+        .i32(length)
+        .u8(6);
+    for i in 0..length {
+        builder = builder.bytes(&(i as f64).to_le_bytes());
+    }
+    let bytes = builder.message_end().build();
+    assert!(length as usize > ParseOptions::default().max_array_length.unwrap());
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let Value::Array(lengths, _, values) = value else {
+        panic!("not an array");
+    };
+    assert_eq!(lengths, [length as usize]);
+    assert_eq!(values[1_999_999], Value::F64(1_999_999.0));
+
+    // A length beyond the input fails once the input ends, without allocating for it up front.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(15)
+        .i32(1)
+        .i32(i32::MAX)
+        .u8(6)
+        .build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+}
+
+#[test]
+fn array_rank_is_validated() {
+    for rank in [0, -1, 33] {
+        // BinaryArray, Single, followed by lengths which must not be read.
+        let bytes = StreamBuilder::new()
+            .header(1)
+            .u8(7)
+            .i32(1)
+            .u8(0)
+            .i32(rank)
+            .i32(1)
+            .build();
+
+        let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidArrayRank { rank: r } if r == rank));
+    }
+}
+
+#[test]
+fn array_size_overflow_is_an_error() {
+    // BinaryArray, Rectangular, rank 3, of primitive Int32.
+    let length = i32::MAX;
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(2)
+        .i32(3)
+        .i32(length)
+        .i32(length)
+        .i32(length)
+        .u8(0)
+        .u8(8)
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    let ParseErrorKind::ArraySizeOverflow { lengths } = error.kind else {
+        panic!("unexpected error {error}");
+    };
+    assert_eq!(lengths, [length as usize; 3]);
+}
+
+#[test]
+fn top_level_null_run_is_skipped_at_once() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(14)
+        .i32(i32::MAX)
+        .object_string(1, "x")
+        .message_end()
+        .build();
+
+    // Runs this long are rejected by default.
+    let options = ParseOptions {
+        max_array_length: None,
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    assert_eq!(value, Value::String("x".to_owned()));
+}
+
+#[test]
+fn long_null_run_is_limited_by_default() {
+    // A BinaryArray of i32::MAX objects, all of them in a single ObjectNullMultiple record.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(i32::MAX)
+        .u8(2)
+        .u8(14)
+        .i32(i32::MAX)
+        .message_end()
+        .build();
+    assert_eq!(bytes.len(), 38);
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    let limit = ParseOptions::default().max_array_length.unwrap();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong { length, limit: l } if length == i32::MAX as usize && l == limit
+    ));
+
+    // Without the array length in the way, the run itself is checked.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(3)
+        .u8(14)
+        .i32(i32::MAX)
+        .message_end()
+        .build();
+    let options = ParseOptions {
+        max_array_length: Some(10),
+        ..Default::default()
+    };
+    let error = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong { length, limit: 10 } if length == i32::MAX as usize
+    ));
+}
+
+#[test]
+fn negative_null_count_is_an_error() {
+    // An ArraySingleObject of three elements, given as a run of -1 nulls.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(3)
+        .u8(14)
+        .i32(-1)
+        .message_end()
+        .build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::InvalidNullCount { count: -1 }
+    ));
+}
+
+#[test]
+fn negative_single_array_length_is_an_error() {
+    // ArraySinglePrimitive of i32, ArraySingleObject and ArraySingleString.
+    let primitive = StreamBuilder::new()
+        .header(1)
+        .u8(15)
+        .i32(1)
+        .i32(-1)
+        .u8(8)
+        .build();
+    let object = StreamBuilder::new().header(1).u8(16).i32(1).i32(-1).build();
+    let string = StreamBuilder::new()
+        .header(1)
+        .u8(17)
+        .i32(1)
+        .i32(i32::MIN)
+        .build();
+
+    for (bytes, length) in [(primitive, -1), (object, -1), (string, i32::MIN)] {
+        let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+        assert!(
+            matches!(error.kind, ParseErrorKind::InvalidArrayLength { length: l } if l == length)
+        );
+    }
+}
+
+#[test]
+fn shared_references_are_limited_by_default() {
+    let bytes = doubling_references(30);
+    assert!(bytes.len() < 1000);
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    let limit = ParseOptions::default().max_copied_bytes.unwrap();
+    assert!(matches!(error.kind, ParseErrorKind::TooManyCopiedBytes { limit: l } if l == limit));
+
+    // The same structure of moderate size is fine.
+    let value = parse_nrbf(&mut doubling_references(4).as_slice()).unwrap();
+    let mut node = &value;
+    for _ in 0..4 {
+        assert_eq!(node.members().unwrap()["a"], node.members().unwrap()["b"]);
+        node = &node.members().unwrap()["a"];
+    }
+}
+
+#[test]
+fn copies_of_large_strings_are_limited() {
+    // ArraySingleString of a 1000 byte string, followed by 9 references to it.
+    let mut builder = StreamBuilder::new()
+        .header(1)
+        .u8(17)
+        .i32(1)
+        .i32(10)
+        .object_string(2, &"x".repeat(1000));
+    for _ in 0..9 {
+        builder = builder.u8(9).i32(2);
+    }
+    let bytes = builder.message_end().build();
+
+    let options = ParseOptions {
+        max_copied_bytes: Some(5000),
+        ..Default::default()
+    };
+    let error = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::TooManyCopiedBytes { limit: 5000 }
+    ));
+
+    // The first occurrence is not a copy.
+    let options = ParseOptions {
+        max_copied_bytes: Some(10_000),
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    let Value::Array(_, _, values) = value else {
+        panic!("not an array");
+    };
+    assert_eq!(values, vec![Value::String("x".repeat(1000)); 10]);
+}
+
+#[test]
+fn decoded_values_are_limited() {
+    // Two ArraySingleObject records of 600 nulls each.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(2)
+        .u8(16)
+        .i32(2)
+        .i32(600)
+        .u8(14)
+        .i32(600)
+        .u8(16)
+        .i32(3)
+        .i32(600)
+        .u8(14)
+        .i32(600)
+        .message_end()
+        .build();
+
+    let options = ParseOptions {
+        max_values: Some(1000),
+        ..Default::default()
+    };
+    let error = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::TooManyValues { limit: 1000 }
+    ));
+}
+
+/// `depth` nested BinaryArrays of a single object each, the innermost holding a null.
+fn nested_arrays(depth: i32) -> Vec<u8> {
+    let mut builder = StreamBuilder::new().header(1);
+    for id in 1..=depth {
+        builder = builder.u8(7).i32(id).u8(0).i32(1).i32(1).u8(2);
+    }
+    builder.u8(10).message_end().build()
+}
+
+#[test]
+fn nesting_depth_is_limited_by_default() {
+    let error = parse_nrbf(&mut nested_arrays(200_000).as_slice()).unwrap_err();
+    let limit = ParseOptions::default().max_depth.unwrap();
+    assert!(matches!(error.kind, ParseErrorKind::TooDeep { limit: l } if l == limit));
+
+    // Up to the limit, including the null, nesting fits on the stack of a test thread.
+    let value = parse_nrbf(&mut nested_arrays(limit as i32 - 1).as_slice()).unwrap();
+    let mut depth = 0;
+    let mut element = &value;
+    while let Value::Array(_, _, elements) = element {
+        element = &elements[0];
+        depth += 1;
+    }
+    assert_eq!(depth, limit - 1);
+}
+
+#[test]
+fn many_libraries_do_not_overflow_the_stack() {
+    let mut builder = StreamBuilder::new().header(1);
+    for id in 2..100_002 {
+        builder = builder.library(id, "Lib");
+    }
+    let bytes = builder.object_string(1, "x").message_end().build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(value, Value::String("x".to_owned()));
+}
+
+#[test]
+fn call_context_must_be_a_string() {
+    // MethodReturn with an inline call context, which is an Int32 instead of a string.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x20)
+        .u8(8)
+        .i32(5)
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnexpectedPrimitive {
+            expected: PrimitiveType::String,
+            found: PrimitiveType::Int32
+        }
+    ));
+}
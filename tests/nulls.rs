@@ -0,0 +1,114 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::writer::write_nrbf;
+use nrbf_rs::{parse_nrbf, parse_nrbf_with_options, ParseErrorKind, ParseOptions};
+use std::collections::HashMap;
+
+/// A `BinaryArray` header of a single-dimensional array of objects.
+fn object_array(builder: StreamBuilder, id: i32, length: i32) -> StreamBuilder {
+    builder.u8(7).i32(id).u8(0).i32(1).i32(length).u8(2)
+}
+
+fn null_multiple_256(builder: StreamBuilder, count: u8) -> StreamBuilder {
+    builder.u8(13).u8(count)
+}
+
+#[test]
+fn member_nulls_followed_by_array_starting_with_nulls() {
+    // SystemClassWithMembersAndTypes with two object members and an object array member.
+    let builder = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("System.Foo")
+        .i32(3)
+        .lps("a")
+        .lps("b")
+        .lps("c")
+        .u8(2)
+        .u8(2)
+        .u8(5);
+    let builder = null_multiple_256(builder, 2);
+    let builder = object_array(builder, 2, 3);
+    let builder = null_multiple_256(builder, 2);
+    let bytes = builder.object_string(3, "x").message_end().build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let array = Value::Array(
+        vec![3],
+        vec![0],
+        vec![Value::Null, Value::Null, Value::String("x".to_owned())],
+    );
+    assert_eq!(
+        value,
+        Value::Object(
+            "System.Foo".into(),
+            None,
+            HashMap::from([
+                ("a".into(), Value::Null),
+                ("b".into(), Value::Null),
+                ("c".into(), array),
+            ])
+        )
+    );
+}
+
+#[test]
+fn null_run_does_not_leak_out_of_its_array() {
+    let builder = object_array(StreamBuilder::new().header(1), 1, 2);
+    let builder = object_array(builder, 2, 1);
+    let builder = null_multiple_256(builder, 2);
+    let bytes = builder.object_string(3, "x").message_end().build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::NullRunTooLong { excess: 1 }
+    ));
+}
+
+#[test]
+fn null_helpers() {
+    assert!(Value::Null.is_null());
+    assert!(!Value::I32(0).is_null());
+    assert!(Value::Reference(2).is_reference());
+    assert!(!Value::Null.is_reference());
+
+    let member = Value::String("x".to_owned());
+    assert_eq!(Value::Null.as_option(), None);
+    assert_eq!(member.as_option(), Some(&member));
+}
+
+#[test]
+fn null_runs_are_preserved_on_request() {
+    let builder = object_array(StreamBuilder::new().header(1), 1, 3);
+    let builder = null_multiple_256(builder, 2);
+    let bytes = builder.object_string(2, "x").message_end().build();
+
+    let string = Value::String("x".to_owned());
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::array(vec![Value::Null, Value::Null, string.clone()])
+    );
+
+    let options = ParseOptions {
+        preserve_null_runs: true,
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    assert_eq!(
+        value,
+        Value::array(vec![Value::NullRun(2), Value::Null, string])
+    );
+    assert!(value.elements().unwrap()[0].is_null());
+
+    let mut written = Vec::new();
+    write_nrbf(&value, &mut written).unwrap();
+    assert_eq!(written, bytes);
+
+    let too_long = Value::array(vec![Value::NullRun(3), Value::Null]);
+    assert!(write_nrbf(&too_long, &mut Vec::new()).is_err());
+}
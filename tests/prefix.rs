@@ -0,0 +1,126 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    parse_nrbf, parse_nrbf_all, parse_nrbf_all_with_options, parse_nrbf_prefix,
+    parse_nrbf_prefix_with_options, ParseErrorKind, ParseOptions,
+};
+use std::cell::Cell;
+use std::io::Read;
+use std::rc::Rc;
+
+fn message() -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build()
+}
+
+fn envelope() -> Vec<u8> {
+    let mut bytes = message();
+    bytes.extend_from_slice(b"trailer");
+    bytes
+}
+
+#[test]
+fn prefix_returns_message_length() {
+    let bytes = envelope();
+    let mut stream = bytes.as_slice();
+    let (value, length) = parse_nrbf_prefix(&mut stream).unwrap();
+    assert_eq!(value, Value::String("root".to_owned()));
+    assert_eq!(length, message().len() as u64);
+    assert_eq!(stream, b"trailer");
+}
+
+#[test]
+fn parse_leaves_stream_after_message_end() {
+    let bytes = envelope();
+    let mut stream = bytes.as_slice();
+    parse_nrbf(&mut stream).unwrap();
+
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "trailer");
+}
+
+#[test]
+fn concatenated_messages_are_all_parsed() {
+    let second = StreamBuilder::new()
+        .header(5)
+        .object_string(5, "second")
+        .message_end()
+        .build();
+    let bytes = [message(), second.clone()].concat();
+
+    let values = parse_nrbf_all(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        values,
+        [
+            Value::String("root".to_owned()),
+            Value::String("second".to_owned())
+        ]
+    );
+    assert_eq!(parse_nrbf_all(&mut [].as_slice()).unwrap(), []);
+
+    // A second message which ends early is an error, located in the whole stream.
+    let truncated = [message(), second[..second.len() - 1].to_vec()].concat();
+    let error = parse_nrbf_all(&mut truncated.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+    assert_eq!(error.offset, truncated.len() as u64);
+}
+
+/// A message whose root is an ArraySingleObject of three nulls.
+fn null_array() -> Vec<u8> {
+    StreamBuilder::new()
+        .header(1)
+        .u8(16)
+        .i32(1)
+        .i32(3)
+        .u8(13)
+        .u8(3)
+        .message_end()
+        .build()
+}
+
+#[test]
+fn options_apply_to_every_message() {
+    let limited = || ParseOptions {
+        max_array_length: Some(2),
+        ..Default::default()
+    };
+
+    let error =
+        parse_nrbf_prefix_with_options(&mut null_array().as_slice(), limited()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong {
+            length: 3,
+            limit: 2
+        }
+    ));
+
+    let bytes = [message(), null_array()].concat();
+    let error = parse_nrbf_all_with_options(&mut bytes.as_slice(), limited()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ArrayTooLong {
+            length: 3,
+            limit: 2
+        }
+    ));
+
+    let records = Rc::new(Cell::new(0));
+    let counter = records.clone();
+    let options = ParseOptions {
+        on_record: Some(Box::new(move |_| counter.set(counter.get() + 1))),
+        ..Default::default()
+    };
+    let values = parse_nrbf_all_with_options(&mut bytes.as_slice(), options).unwrap();
+    assert_eq!(
+        values[1],
+        Value::Array(vec![3], vec![0], vec![Value::Null; 3])
+    );
+    assert_eq!(records.get(), 7);
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use common::{primitive_member, StreamBuilder};
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    parse_nrbf, read_lps, read_primitive, read_variable_length, BinaryType, ParseErrorKind,
+    PrimitiveType, RecordType,
+};
+
+fn decode(primitive_type: u8, bytes: &[u8]) -> Value {
+    let stream = primitive_member(primitive_type, bytes);
+    let root = parse_nrbf(&mut stream.as_slice()).unwrap();
+    root.members().unwrap()["v"].clone()
+}
+
+#[test]
+fn boolean() {
+    assert_eq!(decode(1, &[0]), Value::Bool(false));
+    assert_eq!(decode(1, &[1]), Value::Bool(true));
+}
+
+#[test]
+fn bytes() {
+    assert_eq!(decode(2, &[0xff]), Value::U8(255));
+    assert_eq!(decode(10, &[0xff]), Value::I8(-1));
+}
+
+#[test]
+fn signed_integers() {
+    // There is no 16-bit variant, 16-bit integers are widened.
+    assert_eq!(decode(7, &(-300i16).to_le_bytes()), Value::I32(-300));
+    assert_eq!(decode(8, &i32::MIN.to_le_bytes()), Value::I32(i32::MIN));
+    assert_eq!(decode(9, &i64::MIN.to_le_bytes()), Value::I64(i64::MIN));
+}
+
+#[test]
+fn unsigned_integers() {
+    assert_eq!(decode(14, &u16::MAX.to_le_bytes()), Value::U32(65535));
+    assert_eq!(decode(15, &u32::MAX.to_le_bytes()), Value::U32(u32::MAX));
+    assert_eq!(decode(16, &u64::MAX.to_le_bytes()), Value::U64(u64::MAX));
+}
+
+#[test]
+fn floats() {
+    let single = decode(11, &0.5f32.to_le_bytes());
+    assert_eq!(single, Value::F32(0.5));
+    assert_eq!(single.to_string(), "0.5f32");
+
+    let double = decode(6, &(-0.25f64).to_le_bytes());
+    assert_eq!(double, Value::F64(-0.25));
+    assert_eq!(double.to_string(), "-0.25f64");
+}
+
+#[test]
+fn nan_and_infinity() {
+    // A signalling NaN with a payload, as used for sentinel values.
+    let bits = 0x7fa0_0001u32;
+    let single = decode(11, &bits.to_le_bytes());
+    let Value::F32(v) = single else {
+        panic!("Expected a Single, got {single:?}");
+    };
+    assert!(v.is_nan());
+    assert_eq!(v.to_bits(), bits);
+    assert_eq!(single.to_string(), "NaNf32");
+    assert_ne!(single, single.clone());
+    assert!(single.eq_bitwise(&single.clone()));
+
+    let double = decode(6, &f64::NEG_INFINITY.to_le_bytes());
+    assert_eq!(double, Value::F64(f64::NEG_INFINITY));
+    assert_eq!(double.to_string(), "-inff64");
+}
+
+#[test]
+fn string() {
+    let mut bytes = vec![5];
+    bytes.extend_from_slice(b"hello");
+    assert_eq!(decode(18, &bytes), Value::String("hello".to_owned()));
+    assert_eq!(decode(18, &bytes).as_str(), Some("hello"));
+    assert_eq!(Value::Decimal("1".to_owned()).as_str(), None);
+}
+
+#[test]
+fn invalid_string_error_shows_its_bytes() {
+    let stream = primitive_member(18, &[3, b'a', 0xff, b'"']);
+    let error = parse_nrbf(&mut stream.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidUtf8(_)));
+    assert_eq!(
+        error.kind.to_string(),
+        "Failed to decode UTF8 data: invalid utf-8 sequence of 1 bytes from index 1, \
+         in the 3 byte string b\"a\\xff\\\"\""
+    );
+}
+
+#[test]
+fn char() {
+    assert_eq!(decode(3, b"a"), Value::Char('a'));
+    assert_eq!(decode(3, "é".as_bytes()), Value::Char('é'));
+    let value = decode(3, "€".as_bytes());
+    assert_eq!(value, Value::Char('€'));
+    assert_eq!(char::try_from(&value), Ok('€'));
+    assert_eq!(
+        char::try_from(&Value::I32(1)),
+        Err("Expected Char; Got I32".to_owned())
+    );
+
+    let error = read_primitive(PrimitiveType::Char, &mut [0xff].as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidUtf8(_)));
+}
+
+#[test]
+fn decimal() {
+    let mut bytes = vec![6];
+    bytes.extend_from_slice(b"-12.50");
+    let value = decode(5, &bytes);
+    assert_eq!(value, Value::Decimal("-12.50".to_owned()));
+    assert_eq!(value.to_string(), "-12.50m");
+}
+
+#[cfg(feature = "rust_decimal")]
+#[test]
+fn decimal_converts_to_rust_decimal() {
+    use rust_decimal::Decimal;
+
+    let decimal = |s: &str| Value::Decimal(s.to_owned()).as_decimal();
+    assert_eq!(decimal("-12.50"), Some(Decimal::new(-1250, 2)));
+    assert_eq!(decimal("7"), Some(Decimal::new(7, 0)));
+    assert_eq!(decimal("1,5"), None);
+    assert_eq!(decimal("1e3"), None);
+    assert_eq!(decimal(".5"), None);
+    assert_eq!(Value::F64(1.5).as_decimal(), None);
+}
+
+#[test]
+fn null() {
+    assert_eq!(decode(17, &[]), Value::Null);
+}
+
+#[test]
+fn read_primitive_matches_member_decoding() {
+    let bytes = (-300i16).to_le_bytes();
+    let value = read_primitive(PrimitiveType::Int16, &mut bytes.as_slice()).unwrap();
+    assert_eq!(value, decode(7, &bytes));
+
+    let error = read_primitive(PrimitiveType::Int32, &mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+
+    let bytes = (-600_000_000i64).to_le_bytes();
+    let value = read_primitive(PrimitiveType::TimeSpan, &mut bytes.as_slice()).unwrap();
+    assert_eq!(value, Value::TimeSpan(-600_000_000));
+    assert_eq!(value, decode(12, &bytes));
+}
+
+#[test]
+fn enums_convert_from_and_to_bytes() {
+    assert_eq!(PrimitiveType::try_from(8).unwrap(), PrimitiveType::Int32);
+    assert_eq!(u8::from(PrimitiveType::String), 18);
+    assert_eq!(BinaryType::try_from(7).unwrap(), BinaryType::PrimitiveArray);
+    assert_eq!(u8::from(BinaryType::Object), 2);
+    assert_eq!(RecordType::try_from(11).unwrap(), RecordType::MessageEnd);
+
+    let error = BinaryType::try_from(8).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnexpectedEnumValue {
+            enum_name: "BinaryType",
+            byte: 8
+        }
+    ));
+}
+
+#[test]
+fn length_prefixed_strings_can_be_read_directly() {
+    // 200 encoded as 0xc8 0x01.
+    let mut stream = [0xc8, 0x01, 0x7f].as_slice();
+    assert_eq!(read_variable_length(&mut stream).unwrap(), 200);
+    assert_eq!(read_variable_length(&mut stream).unwrap(), 127);
+
+    let string = "é".repeat(100);
+    let bytes = StreamBuilder::new().lps(&string).bytes(b"rest").build();
+    let mut stream = bytes.as_slice();
+    assert_eq!(read_lps(&mut stream).unwrap(), string);
+    assert_eq!(stream, b"rest");
+
+    let error = read_variable_length(&mut [0xff; 5].as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidStringLength));
+}
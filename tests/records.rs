@@ -0,0 +1,502 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    parse_nrbf, parse_nrbf_document, parse_nrbf_lenient, parse_nrbf_unresolved,
+    parse_nrbf_with_options, ParseErrorKind, ParseOptions, ParseWarningKind, RecordReader,
+    RecordType, UnknownPolicy,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn peeked_record_type_is_read_again() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "root")
+        .message_end()
+        .build();
+
+    let traces = Rc::new(RefCell::new(Vec::new()));
+    let sink = traces.clone();
+    let options = ParseOptions {
+        on_record: Some(Box::new(move |trace| {
+            sink.borrow_mut().push((trace.record_type, trace.offset))
+        })),
+        ..Default::default()
+    };
+    parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+
+    assert_eq!(
+        *traces.borrow(),
+        vec![
+            (RecordType::SerializationHeader, 0),
+            (RecordType::BinaryObjectString, 17),
+            (RecordType::MessageEnd, 27),
+        ]
+    );
+}
+
+#[test]
+fn unknown_record_type_is_reported_at_its_offset() {
+    let bytes = StreamBuilder::new().header(1).u8(0x42).build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnexpectedEnumValue {
+            enum_name: "RecordType",
+            byte: 0x42
+        }
+    ));
+    assert_eq!(error.offset, 17);
+    assert!(error.to_string().contains(">42"));
+}
+
+#[test]
+fn unassigned_record_type_is_an_error() {
+    // Record types 18 to 20 are not assigned by the format.
+    let bytes = StreamBuilder::new().header(1).u8(18).i32(1).i32(0).build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::UnexpectedEnumValue {
+            enum_name: "RecordType",
+            byte: 18
+        }
+    ));
+}
+
+#[test]
+fn skip_remaining_returns_partial_root() {
+    // A BinaryArray of three objects, interrupted by an unknown record.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(3)
+        .u8(2)
+        .object_string(2, "x")
+        .u8(0x42)
+        .bytes(b"vendor data")
+        .build();
+
+    let options = ParseOptions {
+        on_unknown_record: UnknownPolicy::SkipRemaining,
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    assert_eq!(
+        value,
+        Value::Array(
+            vec![3],
+            vec![0],
+            vec![Value::String("x".to_owned()), Value::Null, Value::Null]
+        )
+    );
+
+    let options = ParseOptions {
+        on_unknown_record: UnknownPolicy::SkipRemaining,
+        ..Default::default()
+    };
+    let document = parse_nrbf_document(&mut bytes.as_slice(), options).unwrap();
+    let [warning] = document.warnings.as_slice() else {
+        panic!("expected one warning, got {:?}", document.warnings);
+    };
+    assert!(matches!(
+        warning.kind,
+        ParseWarningKind::SkippedRemaining { .. }
+    ));
+    // The offset of the unknown record.
+    assert_eq!(warning.offset, 39);
+}
+
+#[test]
+fn lenient_parse_returns_partial_root_and_error() {
+    // A BinaryArray of three objects, whose second string is cut off.
+    let array = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(3)
+        .u8(2)
+        .object_string(2, "x");
+    let complete = array
+        .clone()
+        .object_string(3, "y")
+        .u8(10)
+        .message_end()
+        .build();
+    let truncated = array.u8(6).i32(3).u8(10).bytes(b"ab").build();
+
+    let (value, error) = parse_nrbf_lenient(&mut truncated.as_slice(), ParseOptions::default());
+    let string = |s: &str| Value::String(s.to_owned());
+    assert_eq!(
+        value,
+        Value::array(vec![string("x"), Value::Null, Value::Null])
+    );
+    let error = error.unwrap();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+    assert_eq!(error.offset, truncated.len() as u64);
+
+    let (value, error) = parse_nrbf_lenient(&mut complete.as_slice(), ParseOptions::default());
+    assert_eq!(
+        value,
+        Value::array(vec![string("x"), string("y"), Value::Null])
+    );
+    assert!(error.is_none());
+
+    let (value, error) = parse_nrbf_lenient(&mut [].as_slice(), ParseOptions::default());
+    assert_eq!(value, Value::Null);
+    assert!(matches!(
+        error.unwrap().kind,
+        ParseErrorKind::MissingSerializationHeader
+    ));
+}
+
+#[test]
+fn record_reader_yields_unresolved_objects_and_counts() {
+    // A BinaryArray holding a nested string and a reference to a later top-level string.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(1)
+        .object_string(2, "a")
+        .u8(9)
+        .i32(3)
+        .object_string(3, "b")
+        .message_end()
+        .build();
+
+    let mut stream = bytes.as_slice();
+    let mut reader = RecordReader::new(&mut stream, ParseOptions::default());
+    assert_eq!((reader.records_read(), reader.bytes_consumed()), (0, 0));
+
+    let array = Value::Array(
+        vec![2],
+        vec![0],
+        vec![Value::Reference(2), Value::Reference(3)],
+    );
+    assert_eq!(reader.next().unwrap().unwrap(), (1, array));
+    assert_eq!(reader.root_id(), Some(1));
+    assert_eq!((reader.records_read(), reader.bytes_consumed()), (4, 44));
+
+    let string = |s: &str| Value::String(s.to_owned());
+    assert_eq!(reader.next().unwrap().unwrap(), (2, string("a")));
+    assert_eq!(reader.next().unwrap().unwrap(), (3, string("b")));
+    assert!(reader.next().is_none());
+    assert_eq!((reader.records_read(), reader.bytes_consumed()), (6, 52));
+}
+
+#[test]
+fn eager_resolution_inlines_backward_references() {
+    // A string, then a BinaryArray referencing it and a string further ahead.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(2, "a")
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(1)
+        .u8(9)
+        .i32(2)
+        .u8(9)
+        .i32(3)
+        .object_string(3, "b")
+        .message_end()
+        .build();
+
+    let options = ParseOptions {
+        eager_resolve_known_refs: true,
+        ..Default::default()
+    };
+    let mut stream = bytes.as_slice();
+    let objects = RecordReader::new(&mut stream, options)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let string = |s: &str| Value::String(s.to_owned());
+    let array = Value::Array(vec![2], vec![0], vec![string("a"), Value::Reference(3)]);
+    assert_eq!(
+        objects,
+        vec![(2, string("a")), (1, array), (3, string("b"))]
+    );
+
+    let options = ParseOptions {
+        eager_resolve_known_refs: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap(),
+        parse_nrbf(&mut bytes.as_slice()).unwrap()
+    );
+}
+
+#[test]
+fn duplicate_object_id_is_an_error() {
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .object_string(1, "a")
+        .object_string(1, "b")
+        .message_end()
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::DuplicateObjectId { object_id: 1 }
+    ));
+}
+
+#[test]
+fn unresolved_parse_keeps_shared_objects_once() {
+    // A BinaryArray referencing the same string twice.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(1)
+        .object_string(2, "shared")
+        .u8(9)
+        .i32(2)
+        .message_end()
+        .build();
+
+    let (root, objects) =
+        parse_nrbf_unresolved(&mut bytes.as_slice(), ParseOptions::default()).unwrap();
+    let array = Value::Array(
+        vec![2],
+        vec![0],
+        vec![Value::Reference(2), Value::Reference(2)],
+    );
+    assert_eq!(root, array);
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[&1], array);
+    assert_eq!(objects[&2], Value::String("shared".to_owned()));
+}
+
+#[test]
+fn structural_equality_ignores_object_ids() {
+    let shared_array = |array_id: i32, string_id: i32, s: &str| {
+        StreamBuilder::new()
+            .header(array_id)
+            .u8(7)
+            .i32(array_id)
+            .u8(0)
+            .i32(1)
+            .i32(2)
+            .u8(1)
+            .object_string(string_id, s)
+            .u8(9)
+            .i32(string_id)
+            .message_end()
+            .build()
+    };
+    let parse = |bytes: Vec<u8>| {
+        parse_nrbf_unresolved(&mut bytes.as_slice(), ParseOptions::default()).unwrap()
+    };
+
+    let (a, a_objects) = parse(shared_array(1, 2, "x"));
+    let (b, b_objects) = parse(shared_array(5, 9, "x"));
+    let (c, c_objects) = parse(shared_array(1, 2, "y"));
+    assert_ne!(a, b);
+    assert!(a.structurally_eq(&a_objects, &b, &b_objects));
+    assert!(!a.structurally_eq(&a_objects, &c, &c_objects));
+
+    let resolved = parse_nrbf(&mut shared_array(3, 4, "x").as_slice()).unwrap();
+    assert!(a.structurally_eq(&a_objects, &resolved, &HashMap::new()));
+    assert!(!a.structurally_eq(&HashMap::new(), &resolved, &HashMap::new()));
+}
+
+#[test]
+fn structural_equality_of_cycles() {
+    let node = |next: i32| {
+        Value::object("Node")
+            .field("next", Value::Reference(next))
+            .build()
+    };
+    let a_objects = HashMap::from([(1, node(1))]);
+    let b_objects = HashMap::from([(7, node(7))]);
+    let c_objects = HashMap::from([(7, node(8)), (8, Value::Null)]);
+
+    let (a, b) = (Value::Reference(1), Value::Reference(7));
+    assert!(a.structurally_eq(&a_objects, &b, &b_objects));
+    assert!(!a.structurally_eq(&a_objects, &b, &c_objects));
+}
+
+#[test]
+fn root_without_object_id() {
+    // A MemberPrimitiveTyped record of an Int32 as the root.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(8)
+        .u8(8)
+        .i32(42)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(value, Value::I32(42));
+}
+
+#[test]
+fn boxed_primitives_in_object_arrays() {
+    // A BinaryArray of objects holding a MemberPrimitiveTyped Double and a string.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(2)
+        .u8(8)
+        .u8(6)
+        .bytes(&1.5f64.to_le_bytes())
+        .object_string(2, "x")
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let string = Value::String("x".to_owned());
+    assert_eq!(value, Value::array(vec![Value::F64(1.5), string]));
+}
+
+#[test]
+fn reference_to_undefined_object_is_an_error() {
+    // A BinaryArray referencing object 9, which is never defined.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(7)
+        .i32(1)
+        .u8(0)
+        .i32(1)
+        .i32(1)
+        .u8(2)
+        .u8(9)
+        .i32(9)
+        .message_end()
+        .bytes(b"trailing data")
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ObjectNotDefined { object_id: 9 }
+    ));
+}
+
+#[test]
+fn reference_ids_are_kept_on_request() {
+    // A `Root` object whose members `a` and `b` both refer to object 3, which is defined
+    // inline by `a`. Member `c` is a string record of its own.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .u8(5)
+        .i32(1)
+        .lps("Root")
+        .i32(3)
+        .lps("a")
+        .lps("b")
+        .lps("c")
+        .u8(2)
+        .u8(2)
+        .u8(1)
+        .i32(2)
+        .u8(5)
+        .i32(3)
+        .lps("Shared")
+        .i32(1)
+        .lps("v")
+        .u8(0)
+        .u8(8)
+        .i32(2)
+        .i32(7)
+        .u8(9)
+        .i32(3)
+        .object_string(4, "x")
+        .message_end()
+        .build();
+
+    let shared = Value::object("Shared")
+        .library("lib")
+        .field("v", Value::I32(7))
+        .build();
+    let options = ParseOptions {
+        keep_reference_ids: true,
+        ..Default::default()
+    };
+    let value = parse_nrbf_with_options(&mut bytes.as_slice(), options).unwrap();
+    let referenced = |id, value: &Value| Value::Referenced(id, Box::new(value.clone()));
+    assert_eq!(
+        value,
+        Value::object("Root")
+            .library("lib")
+            .field("a", referenced(3, &shared))
+            .field("b", referenced(3, &shared))
+            .field("c", referenced(4, &Value::String("x".to_owned())))
+            .build()
+    );
+
+    let plain = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(plain.members().unwrap()["a"], shared);
+    assert!(value.structurally_eq(&HashMap::new(), &plain, &HashMap::new()));
+}
+
+#[test]
+fn forward_string_references_resolve_or_fail() {
+    // ClassWithMembersAndTypes with a String member `s` referencing string 3, which is only
+    // defined after the object.
+    let object = StreamBuilder::new()
+        .header(1)
+        .library(2, "lib")
+        .u8(5)
+        .i32(1)
+        .lps("Named")
+        .i32(1)
+        .lps("s")
+        .u8(1)
+        .i32(2)
+        .u8(9)
+        .i32(3);
+
+    let bytes = object
+        .clone()
+        .object_string(3, "pooled")
+        .message_end()
+        .build();
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    let named = Value::object("Named")
+        .library("lib")
+        .field("s", Value::String("pooled".to_owned()))
+        .build();
+    assert_eq!(value, named);
+
+    let bytes = object.clone().message_end().build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(
+        error.kind,
+        ParseErrorKind::ObjectNotDefined { object_id: 3 }
+    ));
+
+    let bytes = object.build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{
+    parse_nrbf, parse_nrbf_all, parse_nrbf_document, ParseErrorKind, ParseOptions, ParseWarning,
+    ParseWarningKind, RecordReader, RecordType,
+};
+
+#[test]
+fn method_call_with_call_context_and_args() {
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(21)
+        .i32(0x22)
+        .u8(18)
+        .lps("Add")
+        .u8(18)
+        .lps("Calc, CalcLib")
+        .u8(18)
+        .lps("ctx")
+        .i32(2)
+        .u8(8)
+        .i32(1)
+        .u8(8)
+        .i32(2)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodCall {
+            method_name: "Add".to_owned(),
+            type_name: "Calc, CalcLib".to_owned(),
+            call_context: Some(Box::new(Value::String("ctx".to_owned()))),
+            args: Some(vec![Value::I32(1), Value::I32(2)]),
+        }
+    );
+    assert_eq!(
+        value.to_compact_string(),
+        "MethodCall { method_name: \"Add\", type_name: \"Calc, CalcLib\", call_context: \"ctx\", args: [1i32, 2i32] }"
+    );
+}
+
+#[test]
+fn method_call_without_optional_fields() {
+    // NoArgs and NoContext.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(21)
+        .i32(0x11)
+        .u8(18)
+        .lps("Run")
+        .u8(18)
+        .lps("Job")
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodCall {
+            method_name: "Run".to_owned(),
+            type_name: "Job".to_owned(),
+            call_context: None,
+            args: None,
+        }
+    );
+}
+
+#[test]
+fn method_return_fields_follow_in_order() {
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x822)
+        .u8(8)
+        .i32(3)
+        .u8(18)
+        .lps("ctx")
+        .i32(1)
+        .u8(1)
+        .u8(1)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodReturn {
+            return_value: Some(Box::new(Value::I32(3))),
+            call_context: Some(Box::new(Value::String("ctx".to_owned()))),
+            args: Some(vec![Value::Bool(true)]),
+        }
+    );
+}
+
+#[test]
+fn method_return_inline_values_of_any_type() {
+    // ReturnValueInline with a string, ArgsInline with a Double, a Boolean and a String.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x812)
+        .u8(18)
+        .lps("done")
+        .i32(3)
+        .u8(6)
+        .bytes(&2.5f64.to_le_bytes())
+        .u8(1)
+        .u8(0)
+        .u8(18)
+        .lps("out")
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodReturn {
+            return_value: Some(Box::new(Value::String("done".to_owned()))),
+            call_context: None,
+            args: Some(vec![
+                Value::F64(2.5),
+                Value::Bool(false),
+                Value::String("out".to_owned()),
+            ]),
+        }
+    );
+}
+
+#[test]
+fn void_method_return_has_no_return_value() {
+    // ReturnValueVoid, NoContext and NoArgs.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x411)
+        .message_end()
+        .build();
+
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodReturn {
+            return_value: None,
+            call_context: None,
+            args: None,
+        }
+    );
+}
+
+#[test]
+fn truncated_inline_args_are_an_error() {
+    // ArgsInline announcing two arguments, of which only one follows.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x12)
+        .i32(2)
+        .u8(8)
+        .i32(1)
+        .build();
+
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::Io(_)));
+    assert_eq!(error.offset, 31);
+}
+
+/// A `MethodCall` of `Log` whose arguments and `LogicalCallContext` are stored in the call array.
+fn method_call_with_call_array() -> Vec<u8> {
+    StreamBuilder::new()
+        .header(0)
+        // ArgsInArray and ContextInArray.
+        .u8(21)
+        .i32(0x48)
+        .u8(18)
+        .lps("Log")
+        .u8(18)
+        .lps("Logger")
+        // The call array, referencing the arguments and the call context.
+        .u8(16)
+        .i32(1)
+        .i32(2)
+        .u8(9)
+        .i32(2)
+        .u8(9)
+        .i32(3)
+        // The arguments: a string with object id 0 and a boxed Int32.
+        .u8(16)
+        .i32(2)
+        .i32(2)
+        .object_string(0, "a")
+        .u8(8)
+        .u8(8)
+        .i32(5)
+        .u8(4)
+        .i32(3)
+        .lps("System.Runtime.Remoting.Messaging.LogicalCallContext")
+        .i32(1)
+        .lps("CorrelationId")
+        .u8(1)
+        .object_string(4, "request-7")
+        .message_end()
+        .build()
+}
+
+#[test]
+fn method_call_values_in_call_array() {
+    let bytes = method_call_with_call_array();
+    let document = parse_nrbf_document(&mut bytes.as_slice(), ParseOptions::default()).unwrap();
+    let call_context = Value::object("System.Runtime.Remoting.Messaging.LogicalCallContext")
+        .field("CorrelationId", Value::String("request-7".to_owned()))
+        .build();
+    assert_eq!(
+        document.root,
+        Value::MethodCall {
+            method_name: "Log".to_owned(),
+            type_name: "Logger".to_owned(),
+            call_context: Some(Box::new(call_context)),
+            args: Some(vec![Value::String("a".to_owned()), Value::I32(5)]),
+        }
+    );
+    assert_eq!(document.root_record_type, Some(RecordType::MethodCall));
+    // The message is not stored under an object id, the string with id 0 is.
+    assert_eq!(document.objects[&0], Value::String("a".to_owned()));
+
+    let messages = parse_nrbf_all(&mut [bytes.clone(), bytes.clone()].concat().as_slice()).unwrap();
+    assert_eq!(messages, [document.root.clone(), document.root]);
+
+    let objects = RecordReader::new(&mut bytes.as_slice(), ParseOptions::default())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let ids = objects.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(ids, [0, 1, 0, 2, 3, 4]);
+    assert!(matches!(objects[0].1, Value::MethodCall { args: None, .. }));
+}
+
+#[test]
+fn method_return_values_in_call_array() {
+    // ArgsIsArray: the call array holds the arguments only.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x214)
+        .u8(16)
+        .i32(1)
+        .i32(2)
+        .u8(8)
+        .u8(8)
+        .i32(1)
+        .object_string(2, "x")
+        .message_end()
+        .build();
+    let value = parse_nrbf(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        value,
+        Value::MethodReturn {
+            return_value: None,
+            call_context: None,
+            args: Some(vec![Value::I32(1), Value::String("x".to_owned())]),
+        }
+    );
+
+    // ReturnValueInArray and ExceptionInArray, without arguments or call context.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x3011)
+        .u8(16)
+        .i32(1)
+        .i32(2)
+        .u8(8)
+        .u8(8)
+        .i32(7)
+        .u8(10)
+        .message_end()
+        .build();
+    let document = parse_nrbf_document(&mut bytes.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(
+        document.root,
+        Value::MethodReturn {
+            return_value: Some(Box::new(Value::I32(7))),
+            call_context: None,
+            args: None,
+        }
+    );
+    assert_eq!(
+        document.warnings,
+        [ParseWarning {
+            kind: ParseWarningKind::IgnoredCallArrayValues { count: 1 },
+            offset: 39,
+        }]
+    );
+}
+
+#[test]
+fn missing_call_array_is_an_error() {
+    // ArgsIsArray, but the message ends right away.
+    let bytes = StreamBuilder::new()
+        .header(0)
+        .u8(22)
+        .i32(0x14)
+        .message_end()
+        .build();
+    let error = parse_nrbf(&mut bytes.as_slice()).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::InvalidCallArray));
+}
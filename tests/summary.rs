@@ -0,0 +1,87 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Map;
+use nrbf_rs::{parse_nrbf_document, ParseOptions, ParseSummary};
+
+#[test]
+fn summary_counts_records() {
+    // A `Root` with a string member and an Int32 array member, and a second `Root` referenced as
+    // element of an object array.
+    let root = |builder: StreamBuilder, id: i32, array_id: i32| {
+        builder
+            .u8(1)
+            .i32(id)
+            .i32(1)
+            .object_string(id + 1, "a")
+            .u8(9)
+            .i32(array_id)
+            .u8(10)
+    };
+    let builder = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("Root")
+        .i32(3)
+        .lps("name")
+        .lps("xs")
+        .lps("next")
+        .u8(1)
+        .u8(7)
+        .u8(2)
+        .u8(8)
+        .object_string(2, "a")
+        .u8(9)
+        .i32(3)
+        .u8(9)
+        .i32(4);
+    let builder = builder.u8(15).i32(3).i32(2).u8(8).i32(5).i32(6);
+    // An object array holding the second instance, inline, and two nulls.
+    let builder = builder.u8(7).i32(4).u8(0).i32(1).i32(3).u8(2);
+    let builder = root(builder, 5, 3).u8(13).u8(2).message_end();
+
+    let stream = builder.build();
+    let document = parse_nrbf_document(&mut stream.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(
+        document.summary(),
+        ParseSummary {
+            object_count: 6,
+            class_counts: Map::from([("Root".into(), 2)]),
+            string_count: 2,
+            array_count: 2,
+            array_element_count: 5,
+        }
+    );
+}
+
+#[test]
+fn strings_are_collected_once_per_record() {
+    // A `Root` with a string and an array of two strings, one of them shared with the root.
+    let stream = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("Root")
+        .i32(2)
+        .lps("name")
+        .lps("tags")
+        .u8(1)
+        .u8(2)
+        .object_string(2, "x")
+        .u8(9)
+        .i32(3)
+        .u8(7)
+        .i32(3)
+        .u8(0)
+        .i32(1)
+        .i32(2)
+        .u8(1)
+        .object_string(4, "y")
+        .u8(9)
+        .i32(2)
+        .message_end()
+        .build();
+    let document = parse_nrbf_document(&mut stream.as_slice(), ParseOptions::default()).unwrap();
+    assert_eq!(document.strings(), ["x", "y"]);
+}
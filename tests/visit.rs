@@ -0,0 +1,66 @@
+mod common;
+
+use common::StreamBuilder;
+use nrbf_rs::value::Value;
+use nrbf_rs::{parse_nrbf_visit, Visitor};
+
+#[derive(Default)]
+struct Trace(Vec<String>);
+
+impl Visitor for Trace {
+    fn record(&mut self, id: i32) {
+        self.0.push(format!("record {id}"));
+    }
+    fn begin_object(&mut self, class_name: &str) {
+        self.0.push(format!("begin {class_name}"));
+    }
+    fn field(&mut self, name: &str) {
+        self.0.push(format!("field {name}"));
+    }
+    fn end_object(&mut self) {
+        self.0.push("end".to_owned());
+    }
+    fn primitive(&mut self, value: &Value) {
+        self.0.push(value.to_string());
+    }
+    fn reference(&mut self, id: i32) {
+        self.0.push(format!("#{id}"));
+    }
+}
+
+#[test]
+fn visits_objects_as_they_are_decoded() {
+    // SystemClassWithMembersAndTypes with a string member `b` and an Int32 member `a`.
+    let bytes = StreamBuilder::new()
+        .header(1)
+        .u8(4)
+        .i32(1)
+        .lps("System.Foo")
+        .i32(2)
+        .lps("b")
+        .lps("a")
+        .u8(1)
+        .u8(0)
+        .u8(8)
+        .object_string(2, "x")
+        .i32(5)
+        .message_end()
+        .build();
+
+    let mut trace = Trace::default();
+    parse_nrbf_visit(&mut bytes.as_slice(), &mut trace).unwrap();
+    assert_eq!(
+        trace.0,
+        [
+            "record 1",
+            "begin System.Foo",
+            "field a",
+            "5i32",
+            "field b",
+            "#2",
+            "end",
+            "record 2",
+            "\"x\"",
+        ]
+    );
+}
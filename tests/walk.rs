@@ -0,0 +1,86 @@
+use nrbf_rs::value::{Map, Value};
+use std::ops::ControlFlow;
+
+/// `Player { Inventory: [Item { Name: "Sword" }, Null], Level: 3i32 }`
+fn player() -> Value {
+    let sword = Value::object("Item")
+        .field("Name", Value::String("Sword".to_owned()))
+        .build();
+    Value::object("Player")
+        .field("Inventory", Value::array(vec![sword, Value::Null]))
+        .field("Level", Value::I32(3))
+        .build()
+}
+
+/// The class or type names of the nodes in the order they are visited.
+fn order(value: &Value, breadth_first: bool) -> Vec<String> {
+    let mut nodes = Vec::new();
+    let f = |value: &Value| {
+        nodes.push(value.class_name().unwrap_or(value.type_name()).to_owned());
+        ControlFlow::Continue(())
+    };
+    let objects = Map::new();
+    let _ = if breadth_first {
+        value.walk_bfs(&objects, f)
+    } else {
+        value.walk_dfs(&objects, f)
+    };
+    nodes
+}
+
+#[test]
+fn walks_depth_and_breadth_first() {
+    let player = player();
+    assert_eq!(
+        order(&player, false),
+        ["Player", "Array", "Item", "String", "Null", "I32"]
+    );
+    assert_eq!(
+        order(&player, true),
+        ["Player", "Array", "I32", "Item", "Null", "String"]
+    );
+}
+
+#[test]
+fn walk_stops_on_break() {
+    let player = player();
+    let mut visited = 0;
+    let found = player.walk_dfs(&Map::new(), |value| {
+        visited += 1;
+        if value.class_name() == Some("Item") {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert!(found.is_break());
+    assert_eq!(visited, 3);
+}
+
+#[test]
+fn walk_follows_references_once() {
+    // Object 1 references itself and object 2 twice.
+    let root = Value::object("Root")
+        .field("a", Value::Reference(2))
+        .field("b", Value::Reference(2))
+        .field("self", Value::Reference(1))
+        .build();
+    let objects = Map::from([(1, root.clone()), (2, Value::I32(7))]);
+
+    let mut nodes = Vec::new();
+    let _ = Value::Reference(1).walk_dfs(&objects, |value| {
+        nodes.push(value);
+        ControlFlow::Continue(())
+    });
+    assert_eq!(
+        nodes,
+        [
+            &Value::Reference(1),
+            &root,
+            &Value::Reference(2),
+            &Value::I32(7),
+            &Value::Reference(2),
+            &Value::Reference(1),
+        ]
+    );
+}
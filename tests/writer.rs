@@ -0,0 +1,124 @@
+use nrbf_rs::parse_nrbf;
+use nrbf_rs::value::Value;
+use nrbf_rs::writer::{write_nrbf, WriteError};
+use std::collections::HashMap;
+use std::fs::File;
+
+fn round_trip(value: &Value) -> Value {
+    let mut bytes = Vec::new();
+    write_nrbf(value, &mut bytes).unwrap();
+    parse_nrbf(&mut bytes.as_slice()).unwrap()
+}
+
+#[test]
+fn round_trip_example_dumps() {
+    for path in ["examples/batim.dump", "examples/dorfromantik.dump"] {
+        let value = parse_nrbf(&mut File::open(path).unwrap()).unwrap();
+        assert_eq!(round_trip(&value), value, "{path}");
+    }
+}
+
+#[test]
+fn round_trip_repeated_class() {
+    let point = |x| {
+        Value::Object(
+            "Point".into(),
+            Some("Geometry".into()),
+            HashMap::from([
+                ("x".into(), Value::I32(x)),
+                ("label".into(), Value::String(format!("p{x}"))),
+            ]),
+        )
+    };
+    let value = Value::Array(
+        vec![2, 2],
+        vec![0, 1],
+        vec![point(1), Value::Null, point(2), point(3)],
+    );
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn round_trip_bytes_member() {
+    let value = Value::Object(
+        "Blob".into(),
+        None,
+        HashMap::from([("data".into(), Value::Bytes(vec![0, 1, 254, 255]))]),
+    );
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn round_trip_libraries() {
+    let object = |class_name: &str, library_name: &str, members| {
+        Value::Object(
+            class_name.into(),
+            Some(library_name.into()),
+            HashMap::from(members),
+        )
+    };
+    // The library of the inner object is first written between the members of the outer one.
+    let inner = object("Inner", "Other", [("v".into(), Value::I32(1))]);
+    let value = object("Outer", "Main", [("inner".into(), inner)]);
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn round_trip_offset_primitive_array() {
+    let value = Value::Array(vec![2], vec![5], vec![Value::F64(0.5), Value::F64(1.5)]);
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn primitive_root_is_unsupported() {
+    let result = write_nrbf(&Value::I32(1), &mut Vec::new());
+    assert!(matches!(result, Err(WriteError::Unsupported(_))));
+}
+
+#[test]
+fn round_trip_chars() {
+    let chars = ['a', 'é', '€', '🦀'].map(Value::Char).to_vec();
+    let value = Value::Array(vec![4], vec![0], chars);
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn round_trip_decimals() {
+    let decimal = |s: &str| Value::Decimal(s.to_owned());
+    let value = Value::Object(
+        "Invoice".into(),
+        None,
+        HashMap::from([
+            ("total".into(), decimal("-12.50")),
+            (
+                "items".into(),
+                Value::Array(vec![2], vec![0], vec![decimal("1"), decimal("0.001")]),
+            ),
+        ]),
+    );
+    assert_eq!(round_trip(&value), value);
+}
+
+#[test]
+fn missing_lower_bounds_are_zero() {
+    let value = Value::Array(vec![2], vec![], vec![Value::I32(1), Value::I32(2)]);
+    let expected = Value::Array(vec![2], vec![0], vec![Value::I32(1), Value::I32(2)]);
+    assert_eq!(round_trip(&value), expected);
+}
+
+#[test]
+fn array_shape_must_match_elements() {
+    let invalid = [
+        Value::Array(vec![3], vec![0], vec![Value::I32(1), Value::I32(2)]),
+        Value::Array(vec![2, 2], vec![0, 0], vec![Value::Null; 3]),
+        Value::Array(vec![], vec![], vec![]),
+        Value::Array(vec![2, 1], vec![1], vec![Value::Null; 2]),
+    ];
+    for value in invalid {
+        let result = write_nrbf(&value, &mut Vec::new());
+        assert!(
+            matches!(result, Err(WriteError::Unsupported(_))),
+            "{value:?}"
+        );
+    }
+}